@@ -0,0 +1,312 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use toml;
+use campaign::Campaign;
+use config::asset_path;
+use map::Map;
+use simulation;
+use texture::Texture;
+use error;
+use error::Error;
+use {ASSETS_PATH, MODS_DIR};
+
+// The manifest in each mod's directory
+pub const MOD_MANIFEST: &'static str = "assets.toml";
+
+// Every asset the game can use, by name. Paths are relative to the asset directory.
+// Textures are drawn on walls, floors and ceilings, and sprites are drawn for entities,
+// but both are looked up by the same names. Maps pick their music by name too.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AssetManifest {
+    #[serde(default)]
+    pub textures: BTreeMap<String, String>,
+    #[serde(default)]
+    pub sprites: BTreeMap<String, String>,
+    #[serde(default)]
+    pub sounds: BTreeMap<String, String>,
+    #[serde(default)]
+    pub music: BTreeMap<String, String>,
+    #[serde(default)]
+    pub maps: BTreeMap<String, String>
+}
+
+impl AssetManifest {
+    pub fn load(file_path: &str) -> error::Result<AssetManifest> {
+        let mut contents: String = String::new();
+        File::open(file_path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| Error::missing_asset(file_path, e))?;
+
+        toml::from_str(&contents)
+            .map_err(|e| Error::Config(format!("Invalid asset manifest {}: {}", file_path, e)))
+    }
+
+    // Adds every asset in a mod's manifest, replacing any with the same name.
+    // The mod's paths are relative to its directory.
+    fn apply_mod(&mut self, manifest: AssetManifest, mod_dir: &Path) {
+        let relative = |file_path: String| mod_dir.join(file_path).to_string_lossy().into_owned();
+
+        for (name, file_path) in manifest.textures {
+            self.sprites.remove(&name);
+            self.textures.insert(name, relative(file_path));
+        }
+
+        for (name, file_path) in manifest.sprites {
+            self.textures.remove(&name);
+            self.sprites.insert(name, relative(file_path));
+        }
+
+        for (name, file_path) in manifest.sounds {
+            self.sounds.insert(name, relative(file_path));
+        }
+
+        for (name, file_path) in manifest.music {
+            self.music.insert(name, relative(file_path));
+        }
+
+        for (name, file_path) in manifest.maps {
+            self.maps.insert(name, relative(file_path));
+        }
+    }
+}
+
+// The asset manifest with every mod applied, for looking up assets by name
+#[derive(Clone, Debug)]
+pub struct Assets {
+    pub asset_dir: String,
+    pub manifest: AssetManifest,
+    // The mods that were applied, in the order they were applied
+    pub mods: Vec<String>
+}
+
+impl Assets {
+    // Loads the manifest from the asset directory, then applies each mod in its mods directory
+    // in alphabetical order
+    pub fn load(asset_dir: &str) -> error::Result<Assets> {
+        let mut manifest: AssetManifest = AssetManifest::load(&asset_path(asset_dir, ASSETS_PATH))?;
+        let mut mods: Vec<String> = Vec::new();
+
+        let mods_dir: String = asset_path(asset_dir, MODS_DIR);
+        if Path::new(&mods_dir).is_dir() {
+            let mut mod_names: Vec<String> = fs::read_dir(&mods_dir)?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().join(MOD_MANIFEST).is_file())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect();
+            mod_names.sort();
+
+            for name in mod_names {
+                let mod_dir: PathBuf = Path::new(MODS_DIR).join(&name);
+                let mod_manifest: AssetManifest = AssetManifest::load(&asset_path(asset_dir, &mod_dir.join(MOD_MANIFEST).to_string_lossy()))?;
+                manifest.apply_mod(mod_manifest, &mod_dir);
+
+                println!("Loaded mod {}", name);
+                mods.push(name);
+            }
+        }
+
+        Ok(Assets {
+            asset_dir: asset_dir.to_string(),
+            manifest: manifest,
+            mods: mods
+        })
+    }
+
+    // The manifest itself and every mod's manifest
+    pub fn manifest_paths(&self) -> Vec<String> {
+        let mut manifest_paths: Vec<String> = vec![asset_path(&self.asset_dir, ASSETS_PATH)];
+
+        for name in self.mods.iter() {
+            manifest_paths.push(asset_path(&self.asset_dir, &Path::new(MODS_DIR).join(name).join(MOD_MANIFEST).to_string_lossy()));
+        }
+
+        manifest_paths
+    }
+
+    // Every file the manifest lists, with mods applied, along with the manifests themselves
+    pub fn file_paths(&self) -> Vec<String> {
+        let manifest: &AssetManifest = &self.manifest;
+        let mut file_paths: Vec<String> = self.manifest_paths();
+
+        file_paths.extend(manifest.textures.values()
+            .chain(manifest.sprites.values())
+            .chain(manifest.sounds.values())
+            .chain(manifest.music.values())
+            .chain(manifest.maps.values())
+            .map(|file_path| asset_path(&self.asset_dir, file_path)));
+
+        file_paths
+    }
+
+    // Where the texture or sprite with the given name is
+    pub fn texture_path(&self, name: &str) -> Option<String> {
+        self.manifest.textures.get(name)
+            .or_else(|| self.manifest.sprites.get(name))
+            .map(|file_path| asset_path(&self.asset_dir, file_path))
+    }
+
+    pub fn sound_path(&self, name: &str) -> Option<String> {
+        self.manifest.sounds.get(name).map(|file_path| asset_path(&self.asset_dir, file_path))
+    }
+
+    pub fn music_path(&self, name: &str) -> Option<String> {
+        self.manifest.music.get(name).map(|file_path| asset_path(&self.asset_dir, file_path))
+    }
+
+    pub fn map_path(&self, name: &str) -> Option<String> {
+        self.manifest.maps.get(name).map(|file_path| asset_path(&self.asset_dir, file_path))
+    }
+
+    // Checks every file in the manifest is there, and that every level of the campaign loads and only
+    // uses textures and music the manifest has, reporting everything that's wrong together
+    pub fn check(&self, campaign: &Campaign) -> error::Result<()> {
+        let manifest: &AssetManifest = &self.manifest;
+        let mut errors: Vec<Error> = manifest.textures.values()
+            .chain(manifest.sprites.values())
+            .chain(manifest.sounds.values())
+            .chain(manifest.music.values())
+            .chain(manifest.maps.values())
+            .map(|file_path| asset_path(&self.asset_dir, file_path))
+            .chain(campaign.levels.iter().flat_map(|level| level.textures.values().cloned()))
+            .filter(|file_path| !Path::new(file_path).is_file())
+            .map(|file_path| Error::missing_asset(&file_path, "there's no such file"))
+            .collect();
+
+        for level in campaign.levels.iter() {
+            let map: Map = match simulation::load_map(level) {
+                Ok(map) => map,
+                Err(e) => {
+                    errors.extend(e.flatten());
+                    continue;
+                }
+            };
+
+            for name in map.texture_names.iter() {
+                if self.resolve_texture(name, &level.textures).is_none() {
+                    errors.push(Error::missing_asset(&format!("texture '{}' on {}", name, level.describe()), "it isn't in the asset manifest"));
+                }
+            }
+
+            if let Some(ref name) = map.info.music {
+                if self.music_path(name).is_none() {
+                    errors.push(Error::missing_asset(&format!("music '{}' on {}", name, level.describe()), "it isn't in the asset manifest"));
+                }
+            }
+        }
+
+        Error::batch(errors)
+    }
+
+    // Where the texture with the given name is loaded from: the path in overrides if there is one,
+    // otherwise the manifest
+    fn resolve_texture(&self, name: &str, overrides: &HashMap<String, String>) -> Option<String> {
+        overrides.get(name).cloned().or_else(|| self.texture_path(name))
+    }
+
+    // The file for every texture the map uses, by the map's texture ids.
+    // Textures named in overrides are loaded from the path there instead of the manifest.
+    // Fails with every texture that isn't in the manifest.
+    pub fn texture_paths(&self, map: &Map, overrides: &HashMap<String, String>) -> error::Result<HashMap<u32, String>> {
+        let mut texture_paths: HashMap<u32, String> = HashMap::new();
+        let mut errors: Vec<Error> = Vec::new();
+
+        for (id, name) in map.texture_names.iter().enumerate() {
+            match self.resolve_texture(name, overrides) {
+                Some(file_path) => { texture_paths.insert(id as u32, file_path); },
+                None => errors.push(Error::missing_asset(&format!("texture '{}'", name), "it isn't in the asset manifest"))
+            }
+        }
+
+        Error::batch(errors)?;
+        Ok(texture_paths)
+    }
+
+    // Loads every texture the map uses, keyed by the map's texture ids.
+    // Fails with every texture that couldn't be loaded.
+    pub fn load_textures(&self, map: &Map, overrides: &HashMap<String, String>) -> error::Result<HashMap<u32, Texture>> {
+        let mut textures: HashMap<u32, Texture> = HashMap::new();
+        let mut errors: Vec<Error> = Vec::new();
+
+        for (id, file_path) in self.texture_paths(map, overrides)? {
+            match Texture::load(&file_path) {
+                Ok(texture) => { textures.insert(id, texture); },
+                Err(e) => errors.push(e)
+            }
+        }
+
+        Error::batch(errors)?;
+        Ok(textures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use campaign::Campaign;
+    use error::Error;
+    use super::Assets;
+
+    // A directory of its own for each test run, removed once the test is done with it (even if it fails)
+    struct TempDir {
+        path: PathBuf
+    }
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let nanos: u32 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+            let path: PathBuf = env::temp_dir().join(format!("spimquest_{}_{}_{}", name, process::id(), nanos));
+            fs::create_dir_all(&path).unwrap();
+
+            TempDir {
+                path: path
+            }
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    #[test]
+    fn levels_only_use_assets_in_the_manifest() {
+        let assets: Assets = Assets::load(".").unwrap();
+        assets.check(&Campaign::single("res/maps/level2.txt")).unwrap();
+    }
+
+    #[test]
+    fn reports_every_unknown_name_together() {
+        let temp_dir: TempDir = TempDir::new("unknown_assets");
+        let dir: &PathBuf = &temp_dir.path;
+        fs::write(dir.join("map.txt"), "name = Test\nmusic = waltz\nspawn = 1 1 east\n---\n###\n#.#\n#X#\n").unwrap();
+        fs::write(dir.join("map.toml"), "
+[[tile]]
+color = [0, 0, 0]
+symbol = \"#\"
+name = \"wall\"
+texture = \"wall\"
+
+[[tile]]
+color = [1, 1, 1]
+symbol = \"X\"
+name = \"marble\"
+texture = \"marble\"
+").unwrap();
+
+        let assets: Assets = Assets::load(".").unwrap();
+        let errors: Vec<Error> = assets.check(&Campaign::single(&dir.join("map.txt").to_string_lossy())).unwrap_err().flatten();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().any(|message| message.contains("'marble'")));
+        assert!(messages.iter().any(|message| message.contains("'waltz'")));
+    }
+}
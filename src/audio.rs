@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use sdl2::{Sdl, AudioSubsystem};
+use sdl2::mixer;
+use sdl2::mixer::{Chunk, Channel, Music, Sdl2MixerContext};
+use entity::Archetype;
+use assets::Assets;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Sound {
+    Treasure,
+    DifficultyUp,
+    Death,
+    SchindlerSteps,
+    MonsterGrowl
+}
+
+impl Sound {
+    // The sound an entity makes while it's around, if any
+    pub fn ambient_for(archetype: Archetype) -> Option<Sound> {
+        match archetype {
+            Archetype::Schindler => Some(Sound::SchindlerSteps),
+            Archetype::Monster => Some(Sound::MonsterGrowl),
+            _ => None
+        }
+    }
+}
+
+// Sound effects, by their names in the asset manifest
+const SOUNDS: [(Sound, &'static str); 3] = [
+    (Sound::Treasure, "treasure"),
+    (Sound::DifficultyUp, "difficulty"),
+    (Sound::Death, "death")
+];
+
+// Looping ambient sounds, and a muffled version of each for when there's a wall in the way.
+// Both versions need to be the same length so they stay in step.
+const AMBIENT_SOUNDS: [(Sound, &'static str, &'static str); 2] = [
+    (Sound::SchindlerSteps, "schindler_steps", "schindler_steps_muffled"),
+    (Sound::MonsterGrowl, "monster_growl", "monster_growl_muffled")
+];
+
+// The first EMITTER_SLOTS * 2 channels are kept for emitters, one clear and one muffled channel each.
+// One-shot sounds play on whatever's left.
+const MIXER_CHANNELS: i32 = 32;
+const EMITTER_SLOTS: usize = 8;
+const VOLUME_STEP: f64 = 0.1;
+
+// Emitters further away than this can't be heard at all
+const AUDIBLE_DISTANCE: f64 = 16.0;
+// How far an emitter fades between clear and muffled each update
+const OCCLUSION_FADE: f64 = 0.15;
+
+// Something in the world that makes a looping sound, relative to the listener
+#[derive(Copy, Clone, Debug)]
+pub struct Emitter {
+    pub id: u32,
+    pub sound: Sound,
+    // Radians from straight ahead, clockwise (to the right)
+    pub angle: f64,
+    pub distance: f64,
+    // Whether a wall is in the way
+    pub is_occluded: bool
+}
+
+#[derive(Copy, Clone)]
+struct EmitterSlot {
+    id: u32,
+    sound: Sound,
+    // 0 is clear, 1 is fully muffled
+    occlusion: f64
+}
+
+// Plays the music and sound effects.
+// If there's no audio device, or it fails to open, everything here quietly does nothing,
+// so the game still runs without sound.
+pub struct Audio {
+    device: Option<AudioDevice>,
+    volume: f64,
+    is_muted: bool,
+    music_path: Option<String>
+}
+
+struct AudioDevice {
+    _subsystem: AudioSubsystem,
+    _mixer: Sdl2MixerContext,
+    music: Option<Music<'static>>,
+    sounds: HashMap<Sound, Chunk>,
+    // (clear, muffled)
+    ambient_sounds: HashMap<Sound, (Chunk, Chunk)>,
+    emitter_slots: [Option<EmitterSlot>; EMITTER_SLOTS]
+}
+
+impl EmitterSlot {
+    fn is_playing(&self, emitter: &Emitter) -> bool {
+        (self.id == emitter.id) && (self.sound == emitter.sound)
+    }
+}
+
+impl Drop for AudioDevice {
+    fn drop(&mut self) {
+        // Everything that was loaded has to be freed before the device is closed
+        self.music = None;
+        self.sounds.clear();
+        self.ambient_sounds.clear();
+        mixer::close_audio();
+    }
+}
+
+impl AudioDevice {
+    fn open(sdl_context: &Sdl, assets: &Assets) -> Result<AudioDevice, String> {
+        let subsystem: AudioSubsystem = sdl_context.audio()?;
+        mixer::open_audio(mixer::DEFAULT_FREQUENCY, mixer::DEFAULT_FORMAT, mixer::DEFAULT_CHANNELS, 1024)?;
+        mixer::allocate_channels(MIXER_CHANNELS);
+        mixer::reserve_channels((EMITTER_SLOTS * 2) as i32);
+
+        // Music still plays without MP3 support, as long as it's in some other format
+        let mixer_context: Sdl2MixerContext = mixer::init(mixer::INIT_MP3 | mixer::INIT_OGG)?;
+
+        let mut sounds: HashMap<Sound, Chunk> = HashMap::new();
+        for &(sound, name) in SOUNDS.iter() {
+            match load_sound(assets, name) {
+                Ok(chunk) => { sounds.insert(sound, chunk); },
+                Err(e) => eprintln!("{}", e)
+            }
+        }
+
+        let mut ambient_sounds: HashMap<Sound, (Chunk, Chunk)> = HashMap::new();
+        for &(sound, clear_name, muffled_name) in AMBIENT_SOUNDS.iter() {
+            match (load_sound(assets, clear_name), load_sound(assets, muffled_name)) {
+                (Ok(clear), Ok(muffled)) => { ambient_sounds.insert(sound, (clear, muffled)); },
+                (Err(e), _) | (_, Err(e)) => eprintln!("{}", e)
+            }
+        }
+
+        Ok(AudioDevice {
+            _subsystem: subsystem,
+            _mixer: mixer_context,
+            music: None,
+            sounds: sounds,
+            ambient_sounds: ambient_sounds,
+            emitter_slots: [None; EMITTER_SLOTS]
+        })
+    }
+
+    fn stop_emitter(&mut self, slot: usize) {
+        mixer::channel((slot * 2) as i32).halt();
+        mixer::channel(((slot * 2) + 1) as i32).halt();
+        self.emitter_slots[slot] = None;
+    }
+}
+
+fn load_sound(assets: &Assets, name: &str) -> Result<Chunk, String> {
+    let file_path: String = assets.sound_path(name)
+        .ok_or_else(|| format!("Failed to load sound {}: it isn't in the asset manifest", name))?;
+
+    Chunk::from_file(&file_path).map_err(|e| format!("Failed to load sound {}: {}", file_path, e))
+}
+
+impl Audio {
+    // Sounds are looked up by name in the asset manifest
+    pub fn new(sdl_context: &Sdl, assets: &Assets) -> Audio {
+        let device: Option<AudioDevice> = match AudioDevice::open(sdl_context, assets) {
+            Ok(device) => Some(device),
+            Err(e) => {
+                eprintln!("Failed to open audio, continuing without sound: {}", e);
+                None
+            }
+        };
+
+        let audio = Audio {
+            device: device,
+            volume: 0.8,
+            is_muted: false,
+            music_path: None
+        };
+
+        audio.apply_volume();
+        audio
+    }
+
+    // Audio that never plays anything
+    pub fn disabled() -> Audio {
+        Audio {
+            device: None,
+            volume: 0.0,
+            is_muted: true,
+            music_path: None
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.device.is_some()
+    }
+
+    // Loops the given music. Asking for the music that's already playing doesn't restart it.
+    pub fn play_music(&mut self, file_path: Option<&str>) {
+        if self.music_path.as_ref().map(|path| path.as_str()) == file_path {
+            if self.device.is_some() && !Music::is_playing() {
+                if let Some(music) = self.device.as_ref().and_then(|device| device.music.as_ref()) {
+                    music.play(-1).unwrap_or_else(|e| eprintln!("Failed to play music: {}", e));
+                }
+            }
+
+            return;
+        }
+
+        self.stop_music();
+        self.music_path = file_path.map(|path| path.to_string());
+
+        if let (Some(device), Some(file_path)) = (self.device.as_mut(), file_path) {
+            match Music::from_file(file_path) {
+                Ok(music) => {
+                    music.play(-1).unwrap_or_else(|e| eprintln!("Failed to play music {}: {}", file_path, e));
+                    device.music = Some(music);
+                },
+                Err(e) => eprintln!("Failed to load music {}: {}", file_path, e)
+            }
+        }
+    }
+
+    pub fn stop_music(&mut self) {
+        if let Some(device) = self.device.as_mut() {
+            Music::halt();
+            device.music = None;
+        }
+
+        self.music_path = None;
+    }
+
+    pub fn pause_music(&self) {
+        if self.device.is_some() {
+            Music::pause();
+        }
+    }
+
+    pub fn resume_music(&self) {
+        if self.device.is_some() {
+            Music::resume();
+        }
+    }
+
+    // Plays a sound effect once, on whichever channel is free
+    pub fn play(&self, sound: Sound) {
+        if let Some(chunk) = self.device.as_ref().and_then(|device| device.sounds.get(&sound)) {
+            // Running out of free channels just means this one gets skipped
+            let _ = Channel::all().play(chunk, 0);
+        }
+    }
+
+    // Keeps the looping sounds of the closest emitters playing, positioned around the listener.
+    // Emitters that aren't in the list anymore stop.
+    pub fn update_emitters(&mut self, emitters: &[Emitter]) {
+        let volume: f64 = if self.is_muted { 0.0 } else { self.volume };
+        let device: &mut AudioDevice = match self.device.as_mut() {
+            Some(device) => device,
+            None => return
+        };
+
+        let mut audible: Vec<&Emitter> = emitters.iter()
+            .filter(|emitter| (emitter.distance < AUDIBLE_DISTANCE) && device.ambient_sounds.contains_key(&emitter.sound))
+            .collect();
+        audible.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap());
+        audible.truncate(EMITTER_SLOTS);
+
+        // Free up the slots of emitters that went quiet
+        for slot in 0..EMITTER_SLOTS {
+            if let Some(emitter_slot) = device.emitter_slots[slot] {
+                if !audible.iter().any(|emitter| emitter_slot.is_playing(emitter)) {
+                    device.stop_emitter(slot);
+                }
+            }
+        }
+
+        for emitter in audible {
+            let slot: usize = match device.emitter_slots.iter().position(|slot| slot.map_or(false, |slot| slot.is_playing(emitter))) {
+                Some(slot) => slot,
+                None => {
+                    // There's always a free slot, since there are never more audible emitters than slots
+                    let slot: usize = device.emitter_slots.iter().position(|slot| slot.is_none()).unwrap();
+                    let &(ref clear, ref muffled) = &device.ambient_sounds[&emitter.sound];
+                    let _ = mixer::channel((slot * 2) as i32).play(clear, -1);
+                    let _ = mixer::channel(((slot * 2) + 1) as i32).play(muffled, -1);
+
+                    let occlusion: f64 = if emitter.is_occluded { 1.0 } else { 0.0 };
+                    device.emitter_slots[slot] = Some(EmitterSlot { id: emitter.id, sound: emitter.sound, occlusion: occlusion });
+                    slot
+                }
+            };
+
+            let mut emitter_slot: EmitterSlot = device.emitter_slots[slot].unwrap();
+            let target: f64 = if emitter.is_occluded { 1.0 } else { 0.0 };
+            emitter_slot.occlusion += (target - emitter_slot.occlusion).clamp(-OCCLUSION_FADE, OCCLUSION_FADE);
+            device.emitter_slots[slot] = Some(emitter_slot);
+
+            // SDL_mixer angles go clockwise in degrees, with 0 straight ahead
+            let angle: i16 = (emitter.angle.to_degrees().round() as i32).rem_euclid(360) as i16;
+            let distance: u8 = ((emitter.distance / AUDIBLE_DISTANCE) * 255.0).min(255.0) as u8;
+            let max_volume: f64 = volume * mixer::MAX_VOLUME as f64;
+
+            let clear: Channel = mixer::channel((slot * 2) as i32);
+            let muffled: Channel = mixer::channel(((slot * 2) + 1) as i32);
+            let _ = clear.set_position(angle, distance);
+            let _ = muffled.set_position(angle, distance);
+            clear.set_volume((max_volume * (1.0 - emitter_slot.occlusion)).round() as i32);
+            muffled.set_volume((max_volume * emitter_slot.occlusion).round() as i32);
+        }
+    }
+
+    pub fn stop_emitters(&mut self) {
+        if let Some(device) = self.device.as_mut() {
+            for slot in 0..EMITTER_SLOTS {
+                if device.emitter_slots[slot].is_some() {
+                    device.stop_emitter(slot);
+                }
+            }
+        }
+    }
+
+    // From 0 to 1
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: f64) {
+        self.volume = volume.clamp(0.0, 1.0);
+        self.apply_volume();
+    }
+
+    pub fn volume_up(&mut self) {
+        let volume: f64 = self.volume + VOLUME_STEP;
+        self.set_volume(volume);
+    }
+
+    pub fn volume_down(&mut self) {
+        let volume: f64 = self.volume - VOLUME_STEP;
+        self.set_volume(volume);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.is_muted
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.is_muted = !self.is_muted;
+        self.apply_volume();
+    }
+
+    fn apply_volume(&self) {
+        if self.device.is_none() {
+            return;
+        }
+
+        let volume: i32 = if self.is_muted { 0 } else { (self.volume * mixer::MAX_VOLUME as f64).round() as i32 };
+        Music::set_volume(volume);
+        Channel::all().set_volume(volume);
+    }
+}
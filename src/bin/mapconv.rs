@@ -0,0 +1,57 @@
+extern crate spimquest;
+
+use std::env;
+use std::process;
+use spimquest::map::Map;
+use spimquest::generator;
+use spimquest::generator::{Algorithm, GeneratorSettings};
+
+// Converts maps between the image (.png) and text (.txt) formats.
+// The legend next to the input map is written next to the output map as well.
+// It can also write out a generated map, using the default generator settings.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let map: Map = if (args.len() == 5) && (args[1] == "--generate") {
+        generate(&args[2], &args[3])
+    } else if args.len() == 3 {
+        Map::load(&args[1]).unwrap_or_else(|e| {
+            eprintln!("Failed to load map {}: {}", args[1], e);
+            process::exit(1);
+        })
+    } else {
+        eprintln!("Usage: {} <input map> <output map>", args[0]);
+        eprintln!("       {} --generate <recursive_backtracker|prim|rooms_and_corridors> <seed> <output map>", args[0]);
+        eprintln!("Map formats are picked by file extension: .png for images, .txt for text maps.");
+        process::exit(1);
+    };
+
+    let output: &String = args.last().unwrap();
+
+    if let Err(e) = map.save(output) {
+        eprintln!("Failed to save map {}: {}", output, e);
+        process::exit(1);
+    }
+}
+
+fn generate(algorithm: &str, seed: &str) -> Map {
+    let algorithm: Algorithm = Algorithm::from_name(algorithm).unwrap_or_else(|| {
+        eprintln!("Unknown algorithm {}", algorithm);
+        process::exit(1);
+    });
+
+    let seed: u64 = seed.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid seed {}", seed);
+        process::exit(1);
+    });
+
+    let settings = GeneratorSettings {
+        seed: seed,
+        algorithm: algorithm,
+        ..GeneratorSettings::default()
+    };
+
+    generator::generate(&settings).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    })
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use toml;
+use generator::GeneratorSettings;
+use error;
+use error::Error;
+
+// An ordered list of levels, played one after another.
+// Score and stats carry over from one level to the next.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Campaign {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "level")]
+    pub levels: Vec<Level>
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Level {
+    // Path to the map, in any format Map::load understands
+    #[serde(default)]
+    pub map: Option<String>,
+    // How many treasures have to be collected before the exit appears.
+    // Defaults to every treasure on the map, and can't be more than that.
+    #[serde(default)]
+    pub treasure_goal: Option<u32>,
+    // Generate the map instead of loading it
+    // (after treasure_goal, since TOML needs plain values written before tables)
+    #[serde(default)]
+    pub generate: Option<GeneratorSettings>,
+    #[serde(default)]
+    pub difficulty: DifficultyCurve,
+    // Texture names (as used in map legends) to load from a different file on this level
+    #[serde(default)]
+    pub textures: HashMap<String, String>
+}
+
+// How quickly a level gets harder.
+// The difficulty goes up by one every interval seconds, and the interval grows by interval_growth
+// seconds each time. Chasers move at speed_per_difficulty tiles per second for each level of difficulty.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct DifficultyCurve {
+    #[serde(default)]
+    pub start: u32,
+    #[serde(default = "default_interval")]
+    pub interval: f64,
+    #[serde(default = "default_interval_growth")]
+    pub interval_growth: f64,
+    #[serde(default = "default_speed_per_difficulty")]
+    pub speed_per_difficulty: f64
+}
+
+fn default_interval() -> f64 {
+    5.0
+}
+
+fn default_interval_growth() -> f64 {
+    2.0
+}
+
+fn default_speed_per_difficulty() -> f64 {
+    0.5
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> DifficultyCurve {
+        DifficultyCurve {
+            start: 0,
+            interval: default_interval(),
+            interval_growth: default_interval_growth(),
+            speed_per_difficulty: default_speed_per_difficulty()
+        }
+    }
+}
+
+impl DifficultyCurve {
+    // Seconds until the difficulty goes up from the given difficulty
+    pub fn interval_at(&self, difficulty: u32) -> f64 {
+        self.interval + (self.interval_growth * (difficulty - self.start.min(difficulty)) as f64)
+    }
+
+    // Base speed of chasing entities at the given difficulty
+    pub fn entity_speed(&self, difficulty: u32) -> f64 {
+        difficulty as f64 * self.speed_per_difficulty
+    }
+}
+
+impl Level {
+    // The map path, or the generator settings for generated levels
+    pub fn describe(&self) -> String {
+        match (&self.map, &self.generate) {
+            (&Some(ref map), _) => map.clone(),
+            (&None, &Some(ref settings)) => format!("{} seed {}", settings.algorithm.name(), settings.seed),
+            (&None, &None) => String::new()
+        }
+    }
+}
+
+impl Campaign {
+    pub fn load(file_path: &str) -> error::Result<Campaign> {
+        let mut contents: String = String::new();
+        File::open(file_path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| Error::missing_asset(file_path, e))?;
+
+        let campaign: Campaign = toml::from_str(&contents)
+            .map_err(|e| Error::Config(format!("Invalid campaign {}: {}", file_path, e)))?;
+
+        if campaign.levels.is_empty() {
+            return Err(Error::Config(format!("Invalid campaign {}: there are no levels", file_path)));
+        }
+
+        if let Some(index) = campaign.levels.iter().position(|level| level.map.is_some() == level.generate.is_some()) {
+            return Err(Error::Config(format!("Invalid campaign {}: level {} needs either a map or generate settings", file_path, index + 1)));
+        }
+
+        Ok(campaign)
+    }
+
+    // Names the maps and seeds of every level, so each combination gets its own high scores
+    pub fn leaderboard_key(&self) -> String {
+        self.levels.iter().map(|level| level.describe()).collect::<Vec<String>>().join(" + ")
+    }
+
+    // Rewrites the file paths of every level, e.g. to load them from another directory
+    pub fn map_paths<F>(&mut self, map_path: F) where F: Fn(&str) -> String {
+        for level in self.levels.iter_mut() {
+            level.map = level.map.as_ref().map(|file_path| map_path(file_path));
+
+            for file_path in level.textures.values_mut() {
+                *file_path = map_path(file_path);
+            }
+        }
+    }
+
+    // A campaign with just one map in it, using the default difficulty and textures
+    pub fn single(map: &str) -> Campaign {
+        Campaign {
+            name: String::new(),
+            levels: vec![Level {
+                map: Some(map.to_string()),
+                treasure_goal: None,
+                generate: None,
+                difficulty: DifficultyCurve::default(),
+                textures: HashMap::new()
+            }]
+        }
+    }
+
+    // A campaign with just one generated map in it, using the default difficulty
+    pub fn generated(settings: GeneratorSettings) -> Campaign {
+        Campaign {
+            name: String::new(),
+            levels: vec![Level {
+                map: None,
+                treasure_goal: None,
+                generate: Some(settings),
+                difficulty: DifficultyCurve::default(),
+                textures: HashMap::new()
+            }]
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use toml;
+use assets::Assets;
+use campaign::Campaign;
+use error;
+use error::Error;
+use generator::{Algorithm, GeneratorSettings};
+use {WINDOW_WIDTH, WINDOW_HEIGHT, IS_FULLSCREEN, FIELD_OF_VIEW, CAMPAIGN_PATH, CONFIG_PATH};
+
+pub const USAGE: &'static str = "\
+Options (each can also be set in the config file, which is spimquest.toml by default):
+  --config <file>        Read options from this file
+  --map <file>           Play a single map instead of the campaign
+  --seed <seed>          Play a single generated map instead of the campaign
+  --algorithm <name>     How --seed maps are generated: recursive_backtracker, prim or rooms_and_corridors
+  --width <pixels>       Window width
+  --height <pixels>      Window height
+  --fullscreen           Run fullscreen
+  --windowed             Run in a window
+  --fov <degrees>        Horizontal field of view
+  --difficulty <level>   Difficulty every level starts at
+  --assets <dir>         Directory the res directory is in
+  --headless             Simulate without a window, sound or input, then print where the run ended up
+  --ticks <ticks>        How long a headless run lasts at most, at 60 ticks a second
+  --record <file>        Record each run to a file
+  --replay <file>        Play back a recorded run (re-simulated as fast as possible with --headless)
+  --threads <count>      How many threads to render with (one per core by default)
+  --dev                  Reload textures and maps as soon as they change on disk";
+
+// Joins a path to the asset directory. Paths in the game's own files (the campaign, legends, sounds)
+// are relative to the asset directory, which is the current directory by default.
+pub fn asset_path(asset_dir: &str, file_path: &str) -> String {
+    if asset_dir.is_empty() || (asset_dir == ".") {
+        return file_path.to_string();
+    }
+
+    Path::new(asset_dir).join(file_path).to_string_lossy().into_owned()
+}
+
+// Options for running the game, from the config file and then the command line
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    // Play a single map, from a file or generated from a seed, instead of the campaign
+    pub map: Option<String>,
+    pub seed: Option<u64>,
+    pub algorithm: Algorithm,
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub fov: f64,
+    // How many threads to render with, or one per core if it isn't set
+    pub threads: Option<usize>,
+    // Overrides the difficulty every level starts at
+    pub difficulty: Option<u32>,
+    pub asset_dir: String,
+    pub headless: bool,
+    pub ticks: u64,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    // Watches every asset and map, and reloads the current level's when they change
+    pub dev: bool
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            map: None,
+            seed: None,
+            algorithm: GeneratorSettings::default().algorithm,
+            width: WINDOW_WIDTH,
+            height: WINDOW_HEIGHT,
+            fullscreen: IS_FULLSCREEN,
+            fov: FIELD_OF_VIEW,
+            threads: None,
+            difficulty: None,
+            asset_dir: ".".to_string(),
+            headless: false,
+            // Five minutes
+            ticks: 60 * 60 * 5,
+            record: None,
+            replay: None,
+            dev: false
+        }
+    }
+}
+
+impl Config {
+    pub fn load(file_path: &str) -> error::Result<Config> {
+        let mut contents: String = String::new();
+        File::open(file_path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| Error::Config(format!("Failed to read config {}: {}", file_path, e)))?;
+
+        toml::from_str(&contents)
+            .map_err(|e| Error::Config(format!("Invalid config {}: {}", file_path, e)))
+    }
+
+    // Reads the config file (spimquest.toml unless --config picks another), then applies the
+    // command line arguments (without the program name) on top of it
+    pub fn from_args(args: &[String]) -> error::Result<Config> {
+        let invalid = |message: String| Error::Config(message);
+
+        let config_path: Option<&String> = match args.iter().position(|arg| arg == "--config") {
+            Some(i) => Some(args.get(i + 1).ok_or_else(|| invalid("--config needs a file".to_string()))?),
+            None => None
+        };
+
+        let mut config: Config = match config_path {
+            Some(path) => Config::load(path)?,
+            None if Path::new(CONFIG_PATH).exists() => Config::load(CONFIG_PATH)?,
+            None => Config::default()
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().cloned().ok_or_else(|| invalid(format!("{} needs a value", name)));
+
+            match arg.as_str() {
+                "--config" => { value("--config")?; },
+                "--map" => config.map = Some(value("--map")?),
+                "--seed" => config.seed = Some(parse(&value("--seed")?, "--seed")?),
+                "--algorithm" => config.algorithm = parse_algorithm(&value("--algorithm")?)?,
+                "--width" => config.width = parse(&value("--width")?, "--width")?,
+                "--height" => config.height = parse(&value("--height")?, "--height")?,
+                "--fullscreen" => config.fullscreen = true,
+                "--windowed" => config.fullscreen = false,
+                "--fov" => config.fov = parse(&value("--fov")?, "--fov")?,
+                "--threads" => config.threads = Some(parse(&value("--threads")?, "--threads")?),
+                "--difficulty" => config.difficulty = Some(parse(&value("--difficulty")?, "--difficulty")?),
+                "--assets" => config.asset_dir = value("--assets")?,
+                "--headless" => config.headless = true,
+                "--ticks" => config.ticks = parse(&value("--ticks")?, "--ticks")?,
+                "--record" => config.record = Some(value("--record")?),
+                "--replay" => config.replay = Some(value("--replay")?),
+                "--dev" => config.dev = true,
+                _ => return Err(invalid(format!("Unknown option {}", arg)))
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> error::Result<()> {
+        let invalid = |message: &str| Err(Error::Config(message.to_string()));
+
+        if self.map.is_some() && self.seed.is_some() {
+            return invalid("Pick either a map or a seed, not both");
+        }
+
+        if (self.width == 0) || (self.height == 0) {
+            return invalid("The resolution has to be at least 1x1");
+        }
+
+        if (self.fov <= 0.0) || (self.fov >= 180.0) {
+            return invalid("The field of view has to be between 0 and 180 degrees");
+        }
+
+        if self.threads == Some(0) {
+            return invalid("There has to be at least 1 render thread");
+        }
+
+        if self.record.is_some() && (self.replay.is_some() || self.headless) {
+            return invalid("Only runs played in a window can be recorded");
+        }
+
+        Ok(())
+    }
+
+    pub fn asset_path(&self, file_path: &str) -> String {
+        asset_path(&self.asset_dir, file_path)
+    }
+
+    // The campaign to play: a single map if one was picked, otherwise the campaign file.
+    // Maps can be picked by their names in the asset manifest, or by file path.
+    // File paths in the campaign file are made relative to the asset directory.
+    pub fn campaign(&self, assets: &Assets) -> error::Result<Campaign> {
+        let mut campaign: Campaign = if let Some(ref map) = self.map {
+            Campaign::single(&assets.map_path(map).unwrap_or_else(|| map.clone()))
+        }
+        else if let Some(seed) = self.seed {
+            Campaign::generated(GeneratorSettings {
+                seed: seed,
+                algorithm: self.algorithm,
+                ..GeneratorSettings::default()
+            })
+        }
+        else {
+            let mut campaign: Campaign = Campaign::load(&self.asset_path(CAMPAIGN_PATH))?;
+
+            for (index, level) in campaign.levels.iter_mut().enumerate() {
+                if let Some(file_path) = level.map.as_ref().and_then(|map| assets.manifest.maps.get(map)) {
+                    level.map = Some(file_path.clone());
+                }
+
+                if let Some(name) = level.textures.keys().find(|name| assets.texture_path(name).is_none()) {
+                    return Err(Error::Config(format!("Unknown texture '{}' in campaign level {}", name, index + 1)));
+                }
+            }
+
+            campaign.map_paths(|file_path| self.asset_path(file_path));
+            campaign
+        };
+
+        if let Some(difficulty) = self.difficulty {
+            for level in campaign.levels.iter_mut() {
+                level.difficulty.start = difficulty;
+            }
+        }
+
+        Ok(campaign)
+    }
+}
+
+fn parse<T: ::std::str::FromStr>(value: &str, name: &str) -> error::Result<T> {
+    value.parse().map_err(|_| Error::Config(format!("Invalid value for {}: {}", name, value)))
+}
+
+fn parse_algorithm(name: &str) -> error::Result<Algorithm> {
+    Algorithm::from_name(name).ok_or_else(|| Error::Config(format!("Unknown algorithm {}", name)))
+}
@@ -0,0 +1,90 @@
+use sdl2::{Sdl, GameControllerSubsystem, HapticSubsystem};
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use sdl2::haptic::Haptic;
+
+// Keeps track of the plugged in game controllers, opening them as they're plugged in
+// and closing them as they're pulled out. Their input goes through Input like the keyboard's.
+pub struct Controllers {
+    subsystem: Option<GameControllerSubsystem>,
+    haptic_subsystem: Option<HapticSubsystem>,
+    pads: Vec<Pad>
+}
+
+struct Pad {
+    controller: GameController,
+    // Not every controller can rumble
+    haptic: Option<Haptic>
+}
+
+impl Controllers {
+    // Controllers that are already plugged in show up as ControllerDeviceAdded events, same as new ones
+    pub fn new(sdl_context: &Sdl) -> Controllers {
+        let subsystem: Option<GameControllerSubsystem> = sdl_context.game_controller()
+            .map_err(|e| eprintln!("Failed to initialize game controllers: {}", e))
+            .ok();
+
+        let haptic_subsystem: Option<HapticSubsystem> = sdl_context.haptic()
+            .map_err(|e| eprintln!("Failed to initialize rumble: {}", e))
+            .ok();
+
+        Controllers {
+            subsystem: subsystem,
+            haptic_subsystem: haptic_subsystem,
+            pads: Vec::new()
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            // which is the device index here...
+            Event::ControllerDeviceAdded { which, .. } => {
+                self.open(which as u32);
+            },
+            // ...and the instance id here
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.pads.retain(|pad| pad.controller.instance_id() != which);
+            },
+
+            _ => {}
+        }
+    }
+
+    fn open(&mut self, index: u32) {
+        let controller: GameController = match self.subsystem.as_ref().map(|subsystem| subsystem.open(index)) {
+            Some(Ok(controller)) => controller,
+            Some(Err(e)) => {
+                eprintln!("Failed to open game controller {}: {}", index, e);
+                return;
+            },
+            None => return
+        };
+
+        if self.pads.iter().any(|pad| pad.controller.instance_id() == controller.instance_id()) {
+            return;
+        }
+
+        let haptic: Option<Haptic> = self.haptic_subsystem.as_ref()
+            .and_then(|haptic_subsystem| haptic_subsystem.open_from_joystick_id(index as i32).ok());
+
+        println!("Game controller connected: {}", controller.name());
+
+        self.pads.push(Pad {
+            controller: controller,
+            haptic: haptic
+        });
+    }
+
+    pub fn count(&self) -> usize {
+        self.pads.len()
+    }
+
+    // Rumbles every controller that can, at a strength from 0 to 1
+    pub fn rumble(&mut self, strength: f32, duration_ms: u32) {
+        for pad in self.pads.iter_mut() {
+            if let Some(haptic) = pad.haptic.as_mut() {
+                haptic.rumble_play(strength, duration_ms);
+            }
+        }
+    }
+}
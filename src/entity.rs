@@ -0,0 +1,160 @@
+use pathfinding::astar;
+use map::{Map, Position};
+
+// What kind of thing an entity is, which decides how it behaves
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Archetype {
+    // Chases the player, and kills them on contact
+    Schindler,
+    // Like Schindler, but slower
+    Monster,
+    // Collected by the player for score
+    Treasure,
+    // Decoration that just sits there
+    Prop,
+    // Takes the player to the next level
+    Exit
+}
+
+impl Archetype {
+    pub fn follows_player(&self) -> bool {
+        match *self {
+            Archetype::Schindler | Archetype::Monster => true,
+            Archetype::Treasure | Archetype::Prop | Archetype::Exit => false
+        }
+    }
+
+    pub fn is_deadly(&self) -> bool {
+        self.follows_player()
+    }
+
+    // Multiplier on the base entity speed
+    pub fn speed_factor(&self) -> f64 {
+        match *self {
+            Archetype::Monster => 0.75,
+            _ => 1.0
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Entity {
+    pub x: f64,
+    pub y: f64,
+    // Where the entity was before the last tick, so rendering can smooth out the movement in between
+    pub previous_x: f64,
+    pub previous_y: f64,
+    pub destination: Position,
+    pub texture_id: u32,
+    pub archetype: Archetype,
+    pub follow_player: bool,
+    pub solid: bool,
+    pub id: u32,
+    pub is_deleted: bool
+}
+
+impl Entity {
+    pub fn new(id: u32, x: f64, y: f64, texture_id: u32, archetype: Archetype, solid: bool) -> Entity {
+        Entity {
+            id: id,
+            x: x,
+            y: y,
+            previous_x: x,
+            previous_y: y,
+            texture_id: texture_id,
+            destination: Position::new(0, 0),
+            archetype: archetype,
+            follow_player: archetype.follows_player(),
+            solid: solid,
+            is_deleted: false
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Sprite {
+    pub x: f64,
+    pub y: f64,
+    pub texture_id: u32
+}
+
+impl Sprite {
+    pub fn new(x: f64, y: f64, texture_id: u32) -> Sprite {
+        Sprite {
+            x: x,
+            y: y,
+            texture_id: texture_id
+        }
+    }
+}
+
+// Entity AI
+impl Map {
+    pub fn pathfind(&mut self, goal: Position) {
+        // Paths are found first and applied afterwards, since pathfinding needs to look at the whole map
+        let mut destinations: Vec<(usize, Position)> = Vec::new();
+
+        for (index, ent) in self.entities.iter().enumerate() {
+            if ent.follow_player == false {
+                continue;
+            }
+
+            let start: Position = Position::new(ent.x as i32, ent.y as i32);
+
+            // Ties between equally short paths are broken by the order get_neighbors lists tiles in,
+            // so the same map and player position always give the same path (which replays rely on)
+            let result = astar(&start.clone(), |p| self.get_neighbors(p.clone()), |p| Position::distance(p, &goal), |p| *p == goal);
+            // Chasers with no way to reach the player stay where they're headed
+            if let Some(value) = result {
+                // If total cost of path is greater than 1
+                if value.1 > 1 {
+                    // Set position to next node
+                    //ent.x = value.0[1].x as f64 + 0.5;
+                    //ent.y = value.0[1].y as f64 + 0.5;
+                    destinations.push((index, value.0[1]));
+                }
+            }
+        }
+
+        for (index, destination) in destinations {
+            self.entities[index].destination = destination;
+        }
+    }
+
+    pub fn entity_movement(&mut self, ent_speed: f64, delta_time: f64) {
+        for ent in self.entities.iter_mut() {
+            ent.previous_x = ent.x;
+            ent.previous_y = ent.y;
+
+            if ent.follow_player == false {
+                continue;
+            }
+
+            // TODO: get this outta here
+            let ent_speed = ent_speed * delta_time * ent.archetype.speed_factor();
+
+            if ent.x > (ent.destination.x as f64) {
+                ent.x -= ent_speed;
+            }
+            else if ent.x < (ent.destination.x as f64) {
+                ent.x += ent_speed;
+            }
+
+            if ((ent.destination.x as f64) - ent.x).abs() <= 0.05 {
+                ent.x = ent.destination.x as f64;
+            }
+
+            if (ent.destination.y as f64) > ent.y {
+                ent.y += ent_speed;
+            }
+            else if (ent.destination.y as f64) < ent.y {
+                ent.y -= ent_speed;
+            }
+
+            if ((ent.destination.y as f64) - ent.y).abs() <= 0.05 {
+                ent.y = ent.destination.y as f64;
+            }
+        }
+    }
+}
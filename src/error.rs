@@ -0,0 +1,113 @@
+use std::error;
+use std::fmt;
+use std::io;
+use sdl2::pixels::PixelFormatEnum;
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+// Everything that can go wrong loading the game and its assets
+#[derive(Debug)]
+pub enum Error {
+    // An asset file that isn't there or can't be read
+    MissingAsset { path: String, reason: String },
+    // An image in a pixel format that textures can't be made from
+    UnsupportedPixelFormat { path: String, format: PixelFormatEnum },
+    // A map, legend or set of generator settings that doesn't make a playable level
+    InvalidMap(String),
+    // SDL, or one of its subsystems, the window or the renderer, failed to start
+    Sdl(String),
+    // Bad command line options, config file, controls or campaign
+    Config(String),
+    // A save, recording or high score table that can't be read back, or no longer fits the game
+    Corrupted(String),
+    // Several things that went wrong loading assets and levels, reported together so they can all be fixed at once
+    Assets(Vec<Error>),
+    // Anything else that went wrong reading or writing a file
+    Io(io::Error)
+}
+
+impl Error {
+    pub fn missing_asset<E: fmt::Display>(path: &str, reason: E) -> Error {
+        Error::MissingAsset {
+            path: path.to_string(),
+            reason: reason.to_string()
+        }
+    }
+
+    // The errors this is made up of: every one in a batch, or just this one otherwise
+    pub fn flatten(self) -> Vec<Error> {
+        match self {
+            Error::Assets(errors) => errors.into_iter().flat_map(Error::flatten).collect(),
+            e => vec![e]
+        }
+    }
+
+    // Fails with every error at once, if there were any
+    pub fn batch(mut errors: Vec<Error>) -> Result<()> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::Assets(errors))
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MissingAsset { ref path, ref reason } => write!(f, "Missing asset {}: {}", path, reason),
+            Error::UnsupportedPixelFormat { ref path, format } => write!(f, "Unsupported pixel format {:?} in {}", format, path),
+            Error::InvalidMap(ref message) => write!(f, "{}", message),
+            Error::Sdl(ref message) => write!(f, "SDL error: {}", message),
+            Error::Config(ref message) => write!(f, "{}", message),
+            Error::Corrupted(ref message) => write!(f, "{}", message),
+            Error::Assets(ref errors) => {
+                let is_asset = |e: &Error| matches!(*e, Error::MissingAsset { .. } | Error::UnsupportedPixelFormat { .. });
+
+                if errors.iter().all(is_asset) {
+                    write!(f, "{} assets couldn't be loaded:", errors.len())?;
+                }
+                else {
+                    write!(f, "{} things are wrong with the assets and levels:", errors.len())?;
+                }
+
+                for e in errors.iter() {
+                    write!(f, "\n  {}", e)?;
+                }
+                Ok(())
+            },
+            Error::Io(ref e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+
+    #[test]
+    fn batches_say_what_they_hold() {
+        let missing = || Error::missing_asset("res/wall.png", "there's no such file");
+
+        let assets: String = Error::Assets(vec![missing(), missing()]).to_string();
+        assert!(assets.starts_with("2 assets couldn't be loaded:"));
+
+        let mixed: String = Error::Assets(vec![missing(), Error::InvalidMap("No spawn".to_string())]).to_string();
+        assert!(mixed.starts_with("2 things are wrong with the assets and levels:"));
+    }
+}
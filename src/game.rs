@@ -0,0 +1,1196 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use sdl2::*;
+use sdl2::video::*;
+use sdl2::render::{Canvas, BlendMode};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::event::*;
+use sdl2::keyboard::*;
+use sdl2::controller::Button;
+use sdl2::gfx::primitives::DrawRenderer;
+use time;
+use campaign::{Campaign, Level};
+use simulation::{Simulation, SimEvent, TickInput, TICK_SECONDS};
+use replay;
+use replay::{Recording, Playback};
+use texture::Texture;
+use legend::Legend;
+use watch::FileWatcher;
+use assets::Assets;
+use render;
+use audio::{Audio, Sound, Emitter};
+use input::{Action, Input, Controls, KeyBindings};
+use controller::Controllers;
+use config::Config;
+use error;
+use error::Error;
+use save;
+use save::SaveGame;
+use scores;
+use scores::{HighScores, HighScore};
+use render::{Framebuffer, View, render_world};
+use {WINDOW_TITLE, CONTROLS_PATH, SCORES_PATH, COLOR_BLACK, COLOR_WHITE};
+use {wrap_angle, TWO_PI};
+
+// If rendering falls too far behind, at most MAX_TICKS_PER_FRAME ticks are run to catch up
+// and the rest of the time is dropped, so the game slows down instead of grinding to a halt.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+
+// How much the controls screen changes the mouse sensitivity by
+const MOUSE_SENSITIVITY_STEP: f64 = 0.05;
+
+// Controllers rumble a little when a chaser gets this close (in tiles) without catching the player,
+// at most once every NEAR_MISS_COOLDOWN seconds
+const NEAR_MISS_DISTANCE: f64 = 1.5;
+const NEAR_MISS_COOLDOWN: f64 = 1.0;
+
+// How often dev mode checks for changed assets, in seconds
+const WATCH_INTERVAL: f64 = 0.5;
+
+// What the game is doing, which decides how input is handled and what gets drawn
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GameState {
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+    Victory,
+    // Rebinding keys, from the title or pause screen
+    Controls,
+    // The leaderboard for the campaign, from the title screen
+    HighScores,
+    // Picking a save slot, from the title or pause screen
+    Saves
+}
+
+pub struct Game {
+    config: Config,
+    sdl_context: Sdl,
+    sdl_canvas: Canvas<Window>,
+    state: GameState,
+    is_quitting: bool,
+    framebuffer: Framebuffer,
+    render_thread_count: usize,
+    audio: Audio,
+
+    assets: Assets,
+    // The textures for the current level, and the files they came from, keyed by the map's texture ids
+    textures: HashMap<u32, Texture>,
+    texture_paths: HashMap<u32, String>,
+    // Only in dev mode
+    watcher: Option<FileWatcher>,
+    watch_timer: f64,
+
+    campaign: Campaign,
+    // The run being played, or the last one played
+    simulation: Simulation,
+    // Each run is recorded to recording_path when it's set
+    recording_path: Option<String>,
+    recording: Option<Recording>,
+    // Inputs come from here instead of the player while watching a replay
+    playback: Option<Playback>,
+
+    input: Input,
+    controllers: Controllers,
+    near_miss_cooldown: f64,
+    // The state to go back to when leaving the controls screen
+    controls_return_state: GameState,
+    controls_selection: usize,
+    is_waiting_for_key: bool,
+
+    high_scores: HighScores,
+    // Typing a name for a new high score on the game over or victory screen
+    is_entering_name: bool,
+    player_name: String,
+    // Where the last run placed on the leaderboard
+    high_score_place: Option<usize>,
+
+    // Whether the save slots screen is saving or loading
+    is_saving: bool,
+    saves_return_state: GameState,
+    save_selection: usize,
+    save_descriptions: Vec<String>,
+    // The result of the last save or load, shown on the save slots screen
+    save_message: String
+}
+
+impl Game {
+    // Opens the window and loads the campaign. Nothing is played until run() is called.
+    pub fn new(config: Config) -> error::Result<Game> {
+        let sdl_context: Sdl = ::sdl2::init().map_err(Error::Sdl)?;
+        let sdl_video: VideoSubsystem = sdl_context.video().map_err(Error::Sdl)?;
+
+        sdl_context.mouse().show_cursor(false);
+
+        let mut sdl_window: Window = sdl_video.window(WINDOW_TITLE, config.width, config.height)
+            .position_centered()
+            .opengl()
+            .build()
+            .map_err(|e| Error::Sdl(format!("Failed to create window: {}", e)))?;
+
+        if config.fullscreen {
+            sdl_window.set_fullscreen(FullscreenType::True)
+                .map_err(|e| Error::Sdl(format!("Failed to go fullscreen: {}", e)))?;
+        }
+
+        let sdl_canvas: Canvas<Window> = sdl_window
+            .into_canvas()
+            .target_texture()
+            .present_vsync()
+            .build()
+            .map_err(|e| Error::Sdl(format!("Failed to create renderer: {}", e)))?;
+
+        // Every missing asset is reported at once, before anything is played
+        let assets: Assets = Assets::load(&config.asset_dir)?;
+        let campaign: Campaign = config.campaign(&assets)?;
+        assets.check(&campaign)?;
+
+        let audio: Audio = Audio::new(&sdl_context, &assets);
+        let controllers: Controllers = Controllers::new(&sdl_context);
+
+        let controls: Controls = Controls::load_or_default(Path::new(CONTROLS_PATH)).unwrap_or_else(|e| {
+            eprintln!("{}, using the default controls instead", e);
+            Controls::default()
+        });
+
+        let high_scores: HighScores = HighScores::load_or_default(Path::new(SCORES_PATH)).unwrap_or_else(|e| {
+            eprintln!("{}, starting a new high score table", e);
+            HighScores::default()
+        });
+
+        let watcher: Option<FileWatcher> = if config.dev { Some(FileWatcher::new()) } else { None };
+
+        let mut game = Game {
+            framebuffer: Framebuffer::new(config.width, config.height),
+            render_thread_count: config.threads.unwrap_or_else(render::default_thread_count),
+            config: config,
+            sdl_context: sdl_context,
+            sdl_canvas: sdl_canvas,
+            state: GameState::Title,
+            is_quitting: false,
+            audio: audio,
+            assets: assets,
+            textures: HashMap::new(),
+            texture_paths: HashMap::new(),
+            watcher: watcher,
+            watch_timer: 0.0,
+            simulation: Simulation::new(campaign.clone()),
+            campaign: campaign,
+            recording_path: None,
+            recording: None,
+            playback: None,
+            input: Input::new(controls),
+            controllers: controllers,
+            near_miss_cooldown: 0.0,
+            controls_return_state: GameState::Title,
+            controls_selection: 0,
+            is_waiting_for_key: false,
+            high_scores: high_scores,
+            is_entering_name: false,
+            player_name: String::new(),
+            high_score_place: None,
+            is_saving: false,
+            saves_return_state: GameState::Title,
+            save_selection: 0,
+            save_descriptions: Vec::new(),
+            save_message: String::new()
+        };
+
+        game.watch_assets();
+        Ok(game)
+    }
+
+    // Starts the campaign over from the first level, with a fresh score
+    fn new_game(&mut self) -> error::Result<()> {
+        self.simulation = Simulation::new(self.campaign.clone());
+        self.simulation.start()?;
+        self.start_level()?;
+
+        if self.recording_path.is_some() {
+            self.recording = Some(Recording::new(self.campaign.clone()));
+        }
+
+        self.set_state(GameState::Playing);
+
+        Ok(())
+    }
+
+    // Records every run from now on to the given file, replacing the last one
+    pub fn set_recording_path(&mut self, file_path: &str) {
+        self.recording_path = Some(file_path.to_string());
+    }
+
+    // Watches a recorded run play out, then goes back to the title screen
+    pub fn start_replay(&mut self, recording: Recording) -> error::Result<()> {
+        self.simulation = Simulation::new(recording.campaign.clone());
+        self.simulation.start()?;
+        self.start_level()?;
+
+        println!("Replaying {} ticks", recording.tick_count());
+        self.playback = Some(Playback::new(recording));
+        self.set_state(GameState::Playing);
+
+        Ok(())
+    }
+
+    // Saves the recording or checks the replay once a run is over
+    fn finish_run(&mut self) {
+        if let Some(mut recording) = self.recording.take() {
+            recording.result = Some(self.simulation.result());
+
+            let file_path: String = self.recording_path.clone().unwrap_or_default();
+            match recording.save(&file_path) {
+                Ok(()) => println!("Saved recording {}", file_path),
+                Err(e) => eprintln!("Failed to save recording {}: {}", file_path, e)
+            }
+        }
+
+        if let Some(playback) = self.playback.take() {
+            replay::report(&playback.recording, &self.simulation.result());
+        }
+    }
+
+    fn set_state(&mut self, state: GameState) {
+        match state {
+            GameState::Playing if self.state == GameState::Paused => self.audio.resume_music(),
+            GameState::Paused => self.audio.pause_music(),
+            GameState::Title | GameState::GameOver | GameState::Victory => self.audio.stop_music(),
+            _ => {}
+        }
+
+        if state != GameState::Playing {
+            self.audio.stop_emitters();
+        }
+
+        if let GameState::Title | GameState::GameOver | GameState::Victory = state {
+            self.finish_run();
+        }
+
+        self.state = state;
+
+        // Keys held down when the state changed shouldn't carry over
+        self.input.clear();
+        self.update_mouse_mode();
+    }
+
+    // The mouse is only captured while it's turning the player
+    fn update_mouse_mode(&self) {
+        let is_captured: bool = (self.state == GameState::Playing) && self.input.controls.mouse.look;
+        self.sdl_context.mouse().set_relative_mouse_mode(is_captured);
+    }
+
+    fn set_mouse_look(&mut self, look: bool) {
+        self.input.controls.mouse.look = look;
+        self.input.clear();
+        self.update_mouse_mode();
+    }
+
+    fn save_controls(&self) {
+        if let Err(e) = self.input.controls.save(Path::new(CONTROLS_PATH)) {
+            eprintln!("Failed to save controls {}: {}", CONTROLS_PATH, e);
+        }
+    }
+
+    // Loads the textures and music for the level the simulation just started
+    fn start_level(&mut self) -> error::Result<()> {
+        self.load_level_textures()?;
+
+        self.audio.stop_emitters();
+        let music: Option<String> = self.simulation.map.info.music.as_ref().and_then(|name| self.assets.music_path(name));
+        self.audio.play_music(music.as_ref().map(|path| path.as_str()));
+
+        Ok(())
+    }
+
+    // Loads the textures for the current level's map
+    fn load_level_textures(&mut self) -> error::Result<()> {
+        let level: Level = self.simulation.campaign.levels[self.simulation.level_index].clone();
+        self.texture_paths = self.assets.texture_paths(&self.simulation.map, &level.textures)?;
+        self.textures = self.assets.load_textures(&self.simulation.map, &level.textures)?;
+        Ok(())
+    }
+
+    // In dev mode, watches every file the manifest (with mods applied) lists, and every level's
+    // map, legend and texture overrides
+    fn watch_assets(&mut self) {
+        let watcher: &mut FileWatcher = match self.watcher.as_mut() {
+            Some(watcher) => watcher,
+            None => return
+        };
+
+        watcher.clear();
+
+        for file_path in self.assets.file_paths() {
+            watcher.watch(&file_path);
+        }
+
+        for level in self.campaign.levels.iter() {
+            for file_path in level_map_paths(level).iter().chain(level.textures.values()) {
+                watcher.watch(file_path);
+            }
+        }
+    }
+
+    // Reloads whatever changed in the current level since the last check, in dev mode.
+    // Anything that fails to load (e.g. a file that's only half written) is reported, and the old version is kept.
+    fn reload_changed_assets(&mut self) {
+        let changed: Vec<String> = match self.watcher.as_mut() {
+            Some(watcher) => watcher.changed(),
+            None => return
+        };
+
+        if changed.is_empty() {
+            return;
+        }
+
+        // Assets can be added, or point somewhere else, so everything is looked up again
+        if self.assets.manifest_paths().iter().any(|file_path| changed.contains(file_path)) {
+            match Assets::load(&self.assets.asset_dir) {
+                Ok(assets) => self.assets = assets,
+                Err(e) => {
+                    eprintln!("Failed to reload the asset manifest: {}", e);
+                    return;
+                }
+            }
+
+            self.watch_assets();
+            println!("Reloaded the asset manifest");
+
+            if let Err(e) = self.load_level_textures() {
+                eprintln!("Failed to reload textures: {}", e);
+            }
+
+            return;
+        }
+
+        // A new map can use different textures, so they're all loaded again along with it
+        let level: Level = self.simulation.campaign.levels[self.simulation.level_index].clone();
+        if level_map_paths(&level).iter().any(|file_path| changed.contains(file_path)) {
+            if let Err(e) = self.simulation.reload_map() {
+                eprintln!("Failed to reload the map: {}", e);
+                return;
+            }
+
+            if self.recording.take().is_some() {
+                println!("Stopped recording, since the run can't be replayed once its map has changed");
+            }
+
+            if let Err(e) = self.load_level_textures() {
+                eprintln!("Failed to reload textures: {}", e);
+            }
+
+            return;
+        }
+
+        for file_path in changed.iter() {
+            // Sounds are only loaded when the game starts, and other levels' maps and textures when their level does
+            if !self.texture_paths.values().any(|texture_path| texture_path == file_path) {
+                println!("{} changed, and will be used the next time it's loaded", file_path);
+                continue;
+            }
+
+            for (&id, texture_path) in self.texture_paths.iter() {
+                if texture_path != file_path {
+                    continue;
+                }
+
+                match Texture::load(file_path) {
+                    Ok(texture) => {
+                        self.textures.insert(id, texture);
+                        println!("Reloaded {}", file_path);
+                    },
+                    Err(e) => eprintln!("Failed to reload {}: {}", file_path, e)
+                }
+            }
+        }
+    }
+
+    // Plays until the window is closed or the player quits.
+    // Fails if SDL stops working or a level can't be loaded part way through.
+    pub fn run(&mut self) -> error::Result<()> {
+        let mut last_frame_time: f64 = time::precise_time_s();
+        let mut tick_accumulator: f64 = 0.0;
+
+        let mut sdl_event_pump = self.sdl_context.event_pump()
+            .map_err(Error::Sdl)?;
+
+        // The framebuffer is uploaded into this texture once per frame.
+        // ABGR8888 is laid out as r, g, b, a bytes in memory, which matches the framebuffer.
+        let sdl_texture_creator = self.sdl_canvas.texture_creator();
+        let mut sdl_framebuffer_texture = sdl_texture_creator
+            .create_texture_streaming(PixelFormatEnum::ABGR8888, self.framebuffer.width, self.framebuffer.height)
+            .map_err(|e| Error::Sdl(format!("Failed to create framebuffer texture: {}", e)))?;
+
+        while !self.is_quitting {
+            // Timing
+            let current_time: f64 = time::precise_time_s();
+            let frame_time: f64 = current_time - last_frame_time;
+            last_frame_time = current_time;
+
+            // Handle window events
+            for event in sdl_event_pump.poll_iter() {
+                if let Event::Quit {..} = event {
+                    self.is_quitting = true;
+                }
+
+                // Controllers can be plugged in and pulled out on any screen
+                self.controllers.handle_event(&event);
+
+                // The controls screen needs every key to itself
+                if self.state == GameState::Controls {
+                    self.handle_controls_event(&event);
+                    continue;
+                }
+
+                let actions: Vec<Action> = self.input.handle_event(&event);
+
+                // Names can have the volume keys in them
+                if !self.is_entering_name {
+                    self.handle_volume_actions(&actions);
+                }
+
+                match self.state {
+                    GameState::Title => self.handle_title_event(&event)?,
+                    GameState::Playing => self.handle_playing_event(&event, &actions),
+                    GameState::Paused => self.handle_paused_event(&event, &actions),
+                    GameState::GameOver | GameState::Victory => self.handle_game_end_event(&event)?,
+                    GameState::HighScores => self.handle_high_scores_event(&event),
+                    GameState::Saves => self.handle_saves_event(&event),
+                    GameState::Controls => {}
+                }
+            }
+
+            if self.watcher.is_some() {
+                self.watch_timer += frame_time;
+                if self.watch_timer >= WATCH_INTERVAL {
+                    self.watch_timer = 0.0;
+                    self.reload_changed_assets();
+                }
+            }
+
+            // Simulate
+            let mut ticks: u32 = 0;
+            if self.state == GameState::Playing {
+                tick_accumulator += frame_time;
+
+                while (tick_accumulator >= TICK_SECONDS) && (self.state == GameState::Playing) {
+                    if ticks == MAX_TICKS_PER_FRAME {
+                        tick_accumulator = 0.0;
+                        break;
+                    }
+
+                    self.update()?;
+                    tick_accumulator -= TICK_SECONDS;
+                    ticks += 1;
+                }
+            }
+            else {
+                tick_accumulator = 0.0;
+            }
+
+            // Render, somewhere in between the last two ticks
+            {
+                let interpolation: f64 = if self.state == GameState::Playing { tick_accumulator / TICK_SECONDS } else { 1.0 };
+                let sim: &Simulation = &self.simulation;
+                let view: View = View {
+                    origin_x: sim.previous_player_x + ((sim.player_x - sim.previous_player_x) * interpolation),
+                    origin_y: sim.previous_player_y + ((sim.player_y - sim.previous_player_y) * interpolation),
+                    rotation: wrap_angle(sim.previous_player_rotation + (angle_difference(sim.previous_player_rotation, sim.player_rotation) * interpolation)),
+                    field_of_view: self.config.fov,
+                    interpolation: interpolation
+                };
+
+                self.sdl_canvas.set_draw_color(COLOR_BLACK);
+                self.sdl_canvas.clear();
+
+                if self.is_world_visible() {
+                    self.framebuffer.clear(COLOR_BLACK);
+                    render_world(&mut self.framebuffer, &self.simulation.map, &self.textures, &view, self.render_thread_count);
+
+                    sdl_framebuffer_texture.update(None, &self.framebuffer.pixels, self.framebuffer.pitch())
+                        .map_err(|e| Error::Sdl(format!("Failed to upload framebuffer: {}", e)))?;
+
+                    self.sdl_canvas.copy(&sdl_framebuffer_texture, None, None)
+                        .map_err(Error::Sdl)?;
+                }
+
+                match self.state {
+                    GameState::Title => self.render_title()?,
+                    GameState::Playing => self.render_hud()?,
+                    GameState::Paused => self.render_paused()?,
+                    GameState::GameOver => self.render_game_over()?,
+                    GameState::Victory => self.render_victory()?,
+                    GameState::Controls => self.render_controls()?,
+                    GameState::HighScores => self.render_high_scores()?,
+                    GameState::Saves => self.render_saves()?
+                }
+
+                self.sdl_canvas.present();
+            }
+        }
+
+        self.finish_run();
+
+        Ok(())
+    }
+
+    // Volume can be changed at any time
+    fn handle_volume_actions(&mut self, actions: &[Action]) {
+        for action in actions.iter() {
+            match *action {
+                Action::VolumeDown => self.audio.volume_down(),
+                Action::VolumeUp => self.audio.volume_up(),
+                Action::Mute => self.audio.toggle_mute(),
+                _ => {}
+            }
+        }
+    }
+
+    fn handle_title_event(&mut self, event: &Event) -> error::Result<()> {
+        if is_confirm(event) {
+            return self.new_game();
+        }
+
+        match *event {
+            Event::KeyDown { keycode: Some(Keycode::C), .. } => {
+                self.open_controls();
+            },
+            Event::KeyDown { keycode: Some(Keycode::H), .. } => {
+                self.set_state(GameState::HighScores);
+            },
+            Event::KeyDown { keycode: Some(Keycode::L), .. } => {
+                self.open_saves(false);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                self.is_quitting = true;
+            },
+
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn handle_playing_event(&mut self, event: &Event, actions: &[Action]) {
+        if actions.contains(&Action::ToggleMouseLook) {
+            let look: bool = !self.input.controls.mouse.look;
+            self.set_mouse_look(look);
+            self.save_controls();
+        }
+
+        if actions.contains(&Action::Pause) {
+            self.set_state(GameState::Paused);
+        }
+
+        if let Event::Window { win_event: WindowEvent::FocusLost, .. } = *event {
+            self.set_state(GameState::Paused);
+        }
+    }
+
+    fn handle_paused_event(&mut self, event: &Event, actions: &[Action]) {
+        if actions.contains(&Action::Pause) || is_confirm(event) || is_back(event) {
+            self.set_state(GameState::Playing);
+            return;
+        }
+
+        match *event {
+            Event::KeyDown { keycode: Some(Keycode::C), .. } => {
+                self.open_controls();
+            },
+            Event::KeyDown { keycode: Some(Keycode::S), .. } => {
+                self.open_saves(true);
+            },
+            Event::KeyDown { keycode: Some(Keycode::L), .. } => {
+                self.open_saves(false);
+            },
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } | Event::ControllerButtonDown { button: Button::Back, .. } => {
+                self.set_state(GameState::Title);
+            },
+
+            _ => {}
+        }
+    }
+
+    fn open_saves(&mut self, is_saving: bool) {
+        self.is_saving = is_saving;
+        self.saves_return_state = self.state;
+        self.save_message = String::new();
+        self.update_save_descriptions();
+        self.set_state(GameState::Saves);
+    }
+
+    fn update_save_descriptions(&mut self) {
+        self.save_descriptions = (0..save::SAVE_SLOTS)
+            .map(|slot| match SaveGame::load(slot) {
+                Ok(save) => save.describe(),
+                Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => "Empty".to_string(),
+                Err(_) => "Unreadable save".to_string()
+            })
+            .collect();
+    }
+
+    fn handle_saves_event(&mut self, event: &Event) {
+        let key: Keycode = match *event {
+            Event::KeyDown { keycode: Some(key), repeat: false, .. } => key,
+            _ => return
+        };
+
+        match key {
+            Keycode::Up => {
+                self.save_selection = (self.save_selection + save::SAVE_SLOTS - 1) % save::SAVE_SLOTS;
+            },
+            Keycode::Down => {
+                self.save_selection = (self.save_selection + 1) % save::SAVE_SLOTS;
+            },
+            Keycode::Return if self.is_saving => {
+                let slot: usize = self.save_selection;
+                match SaveGame::capture(&self.simulation).save(slot) {
+                    Ok(()) => {
+                        self.save_message = format!("Saved to slot {}", slot + 1);
+                        println!("{}", self.save_message);
+                    },
+                    Err(e) => {
+                        self.save_message = format!("Failed to save: {}", e);
+                        eprintln!("{}", self.save_message);
+                    }
+                }
+                self.update_save_descriptions();
+            },
+            Keycode::Return => {
+                let slot: usize = self.save_selection;
+                if let Err(e) = self.load_game(slot) {
+                    self.save_message = format!("Failed to load slot {}: {}", slot + 1, e);
+                    eprintln!("{}", self.save_message);
+                }
+            },
+            Keycode::Escape => {
+                let state: GameState = self.saves_return_state;
+                self.set_state(state);
+            },
+
+            _ => {}
+        }
+    }
+
+    // Picks up a saved run where it left off, paused so the player can get ready.
+    // Loaded runs aren't recorded, since a recording has to start at the beginning.
+    fn load_game(&mut self, slot: usize) -> error::Result<()> {
+        let simulation: Simulation = SaveGame::load(slot)?.restore()?;
+
+        self.finish_run();
+        self.simulation = simulation;
+        self.start_level()?;
+
+        self.saves_return_state = GameState::Paused;
+        self.set_state(GameState::Paused);
+
+        Ok(())
+    }
+
+    fn open_controls(&mut self) {
+        self.controls_return_state = self.state;
+        self.controls_selection = 0;
+        self.is_waiting_for_key = false;
+        self.set_state(GameState::Controls);
+    }
+
+    fn handle_controls_event(&mut self, event: &Event) {
+        let key: Keycode = match *event {
+            Event::KeyDown { keycode: Some(key), repeat: false, .. } => key,
+            _ => return
+        };
+
+        let action: Action = Action::ALL[self.controls_selection];
+
+        // The next key pressed gets bound, whatever it is
+        if self.is_waiting_for_key {
+            self.input.controls.keys.add_key(action, key);
+            self.is_waiting_for_key = false;
+            return;
+        }
+
+        match key {
+            Keycode::Up => {
+                self.controls_selection = (self.controls_selection + Action::ALL.len() - 1) % Action::ALL.len();
+            },
+            Keycode::Down => {
+                self.controls_selection = (self.controls_selection + 1) % Action::ALL.len();
+            },
+            Keycode::Return => {
+                self.is_waiting_for_key = true;
+            },
+            Keycode::Backspace | Keycode::Delete => {
+                self.input.controls.keys.set_keys(action, Vec::new());
+            },
+            Keycode::R => {
+                self.input.controls.keys = KeyBindings::default();
+            },
+            Keycode::Tab => {
+                let look: bool = !self.input.controls.mouse.look;
+                self.set_mouse_look(look);
+            },
+            Keycode::Left => {
+                self.input.controls.mouse.sensitivity = (self.input.controls.mouse.sensitivity - MOUSE_SENSITIVITY_STEP).max(MOUSE_SENSITIVITY_STEP);
+            },
+            Keycode::Right => {
+                self.input.controls.mouse.sensitivity += MOUSE_SENSITIVITY_STEP;
+            },
+            Keycode::Escape => {
+                self.save_controls();
+
+                let state: GameState = self.controls_return_state;
+                self.set_state(state);
+            },
+
+            _ => {}
+        }
+    }
+
+    fn handle_high_scores_event(&mut self, event: &Event) {
+        if is_confirm(event) || is_back(event) {
+            self.set_state(GameState::Title);
+        }
+    }
+
+    // Shared by the game over and victory screens
+    fn handle_game_end_event(&mut self, event: &Event) -> error::Result<()> {
+        if self.is_entering_name {
+            self.handle_name_event(event);
+            return Ok(());
+        }
+
+        if is_confirm(event) {
+            self.new_game()?;
+        }
+        else if is_back(event) {
+            self.set_state(GameState::Title);
+        }
+
+        Ok(())
+    }
+
+    // Letters, numbers and spaces go into the name, Enter (or A or Start on a controller) saves the score.
+    // Held keys don't repeat, so a movement key held down at the end of the run doesn't fill the name.
+    fn handle_name_event(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown { keycode: Some(Keycode::Space), repeat: false, .. } => {
+                if self.player_name.len() < scores::MAX_NAME_LENGTH {
+                    self.player_name.push(' ');
+                }
+            },
+            Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                self.player_name.pop();
+            },
+            Event::KeyDown { keycode: Some(Keycode::Return), .. } | Event::ControllerButtonDown { button: Button::A, .. } | Event::ControllerButtonDown { button: Button::Start, .. } => {
+                self.save_high_score();
+            },
+            Event::KeyDown { keycode: Some(Keycode::Escape), .. } | Event::ControllerButtonDown { button: Button::B, .. } => {
+                // Skip saving the score
+                self.is_entering_name = false;
+            },
+            Event::KeyDown { keycode: Some(key), repeat: false, .. } => {
+                let name: String = key.name();
+                let is_character: bool = (name.len() == 1) && name.chars().all(|c| c.is_ascii_alphanumeric());
+                if is_character && (self.player_name.len() < scores::MAX_NAME_LENGTH) {
+                    self.player_name.push_str(&name);
+                }
+            },
+
+            _ => {}
+        }
+    }
+
+    // Ends the run on the game over or victory screen, asking for a name if the score made the leaderboard.
+    // Replays don't get to set high scores.
+    fn end_run(&mut self, state: GameState) {
+        let is_replay: bool = self.playback.is_some();
+        self.set_state(state);
+
+        self.high_score_place = None;
+        self.is_entering_name = !is_replay && self.high_scores.qualifies(&self.simulation.campaign.leaderboard_key(), self.simulation.score);
+    }
+
+    fn save_high_score(&mut self) {
+        let name: String = match self.player_name.trim() {
+            "" => "Player".to_string(),
+            name => name.to_string()
+        };
+        self.player_name = name.clone();
+        self.is_entering_name = false;
+
+        let high_score = HighScore {
+            name: name,
+            score: self.simulation.score,
+            difficulty: self.simulation.difficulty,
+            survival_time: self.simulation.play_time,
+            map: self.simulation.map.info.name.clone(),
+            date: time::strftime("%Y-%m-%d", &time::now()).unwrap_or_default()
+        };
+
+        let key: String = self.simulation.campaign.leaderboard_key();
+        self.high_score_place = self.high_scores.add(&key, high_score);
+
+        if let Err(e) = self.high_scores.save(Path::new(SCORES_PATH)) {
+            eprintln!("Failed to save high scores {}: {}", SCORES_PATH, e);
+        }
+    }
+
+    // Moves everything along by one tick of play
+    fn update(&mut self) -> error::Result<()> {
+        let playback_input: Option<Option<TickInput>> = self.playback.as_mut().map(|playback| playback.next());
+        let input: TickInput = match playback_input {
+            Some(Some(input)) => input,
+            Some(None) => {
+                // The replay is over
+                self.set_state(GameState::Title);
+                return Ok(());
+            },
+            None => self.read_tick_input()
+        };
+
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(input);
+        }
+
+        let events: Vec<SimEvent> = self.simulation.tick(&input)?;
+
+        for event in events {
+            match event {
+                SimEvent::TreasureCollected => self.audio.play(Sound::Treasure),
+                SimEvent::DifficultyUp => self.audio.play(Sound::DifficultyUp),
+                SimEvent::LevelStarted => {
+                    self.start_level()?;
+                },
+                SimEvent::Died => {
+                    self.end_run(GameState::GameOver);
+                    self.audio.play(Sound::Death);
+                    self.controllers.rumble(1.0, 600);
+                },
+                SimEvent::Victory => self.end_run(GameState::Victory)
+            }
+        }
+
+        self.check_near_miss(TICK_SECONDS);
+
+        if self.state == GameState::Playing {
+            self.update_emitters();
+        }
+
+        Ok(())
+    }
+
+    // Works out how the player wants to move this tick from whatever they're holding
+    fn read_tick_input(&mut self) -> TickInput {
+        let delta_time: f64 = TICK_SECONDS;
+        let rotation_speed: f64 = f64::to_radians(180.0);
+
+        // Keys and buttons push all the way, sticks can push part of the way.
+        // With mouse look, the mouse turns and the turn keys strafe.
+        let is_mouse_look: bool = self.input.controls.mouse.look;
+        let held = |action: Action| if self.input.is_held(action) { 1.0 } else { 0.0 };
+
+        let forward: f64 = self.input.amount(Action::MoveForward) - self.input.amount(Action::MoveBackward);
+        let mut strafe: f64 = self.input.amount(Action::StrafeRight) - self.input.amount(Action::StrafeLeft);
+        let key_turn: f64 = held(Action::TurnRight) - held(Action::TurnLeft);
+        let stick_turn: f64 = self.input.stick_amount(Action::TurnRight) - self.input.stick_amount(Action::TurnLeft);
+
+        let mut turn: f64 = stick_turn * self.input.controls.controller.turn_speed.to_radians() * delta_time;
+        if is_mouse_look {
+            strafe = (strafe + key_turn).clamp(-1.0, 1.0);
+            turn += self.input.take_mouse_turn();
+        }
+        else {
+            turn += key_turn * rotation_speed * delta_time;
+        }
+
+        TickInput {
+            forward: forward,
+            strafe: strafe,
+            turn: turn
+        }
+    }
+
+    fn check_near_miss(&mut self, delta_time: f64) {
+        self.near_miss_cooldown = (self.near_miss_cooldown - delta_time).max(0.0);
+        if (self.state != GameState::Playing) || (self.near_miss_cooldown > 0.0) {
+            return;
+        }
+
+        let (player_x, player_y) = (self.simulation.player_x, self.simulation.player_y);
+        let is_near_miss: bool = self.simulation.map.entities.iter()
+            .filter(|ent| !ent.is_deleted && ent.archetype.is_deadly())
+            .any(|ent| f64::sqrt(((ent.x + 0.5) - player_x).powi(2) + ((ent.y + 0.5) - player_y).powi(2)) < NEAR_MISS_DISTANCE);
+
+        if is_near_miss {
+            self.controllers.rumble(0.35, 150);
+            self.near_miss_cooldown = NEAR_MISS_COOLDOWN;
+        }
+    }
+
+    // Places the sounds of everything chasing the player around them
+    fn update_emitters(&mut self) {
+        let mut emitters: Vec<Emitter> = Vec::new();
+
+        for ent in self.simulation.map.entities.iter() {
+            let sound: Sound = match Sound::ambient_for(ent.archetype) {
+                Some(sound) if !ent.is_deleted => sound,
+                _ => continue
+            };
+
+            let delta_x: f64 = (ent.x + 0.5) - self.simulation.player_x;
+            let delta_y: f64 = (ent.y + 0.5) - self.simulation.player_y;
+            let distance: f64 = ((delta_x * delta_x) + (delta_y * delta_y)).sqrt();
+            let direction: f64 = delta_y.atan2(delta_x);
+
+            // Any wall hit before reaching the entity is in the way
+            let is_occluded: bool = self.simulation.map.raycast(self.simulation.player_x, self.simulation.player_y, direction, distance).is_some();
+
+            emitters.push(Emitter {
+                id: ent.id,
+                sound: sound,
+                angle: direction - self.simulation.player_rotation,
+                distance: distance,
+                is_occluded: is_occluded
+            });
+        }
+
+        self.audio.update_emitters(&emitters);
+    }
+
+    fn render_hud(&mut self) -> error::Result<()> {
+        self.sdl_canvas.string(10, 10, &format!("CSE 341 Difficulty: {}", self.simulation.difficulty), COLOR_WHITE)
+            .map_err(Error::Sdl)?;
+        self.sdl_canvas.string(10, 20, &format!("MIPS Knowledge: {}", self.simulation.score), COLOR_WHITE)
+            .map_err(Error::Sdl)?;
+        self.sdl_canvas.string(10, 30, &format!("{}: {}/{}", self.simulation.map.info.name, self.simulation.level_treasure_collected, self.simulation.level_treasure_goal), COLOR_WHITE)
+            .map_err(Error::Sdl)?;
+
+        Ok(())
+    }
+
+    fn render_title(&mut self) -> error::Result<()> {
+        if self.controllers.count() > 0 {
+            let controllers: String = format!("{} controller(s) connected", self.controllers.count());
+            self.render_text_centered(self.config.height as i32 - 30, &controllers, COLOR_WHITE)?;
+        }
+
+        self.render_text_centered(self.config.height as i32 / 3, WINDOW_TITLE, COLOR_WHITE)?;
+        if !self.campaign.name.is_empty() {
+            let name: String = self.campaign.name.clone();
+            self.render_text_centered((self.config.height as i32 / 3) + 20, &name, COLOR_WHITE)?;
+        }
+
+        self.render_text_centered(self.config.height as i32 / 2, "Press Enter to start", COLOR_WHITE)?;
+        self.render_text_centered((self.config.height as i32 / 2) + 20, "Press C for controls", COLOR_WHITE)?;
+        self.render_text_centered((self.config.height as i32 / 2) + 40, "Press H for high scores", COLOR_WHITE)?;
+        self.render_text_centered((self.config.height as i32 / 2) + 60, "Press L to load a saved game", COLOR_WHITE)?;
+        self.render_text_centered((self.config.height as i32 / 2) + 80, "Press Escape to quit", COLOR_WHITE)?;
+
+        Ok(())
+    }
+
+    fn render_paused(&mut self) -> error::Result<()> {
+        self.render_overlay(Color::RGBA(0, 0, 0, 160))?;
+        self.render_hud()?;
+
+        self.render_text_centered(self.config.height as i32 / 3, "PAUSED", COLOR_WHITE)?;
+        self.render_text_centered(self.config.height as i32 / 2, "Press Escape to resume", COLOR_WHITE)?;
+        self.render_text_centered((self.config.height as i32 / 2) + 20, "Press C for controls, S to save or L to load", COLOR_WHITE)?;
+        self.render_text_centered((self.config.height as i32 / 2) + 40, "Press Backspace to quit to the title screen", COLOR_WHITE)?;
+
+        let volume: String = if !self.audio.is_enabled() {
+            "No audio device".to_string()
+        }
+        else if self.audio.is_muted() {
+            "Volume: muted".to_string()
+        }
+        else {
+            format!("Volume: {}%", (self.audio.volume() * 100.0).round())
+        };
+        self.render_text_centered((self.config.height as i32 / 2) + 60, &volume, COLOR_WHITE)?;
+
+        Ok(())
+    }
+
+    fn render_controls(&mut self) -> error::Result<()> {
+        if self.is_world_visible() {
+            self.render_overlay(Color::RGBA(0, 0, 0, 200))?;
+        }
+
+        let top: i32 = 60;
+        self.render_text_centered(top, "CONTROLS", COLOR_WHITE)?;
+
+        for (i, action) in Action::ALL.iter().enumerate() {
+            let y: i32 = top + 30 + (i as i32 * 14);
+            let keys: Vec<String> = self.input.controls.keys.keys(*action).iter().map(|key| key.name()).collect();
+            let marker: &str = if i == self.controls_selection { ">" } else { " " };
+
+            self.sdl_canvas.string(120, y as i16, &format!("{} {}", marker, action.description()), COLOR_WHITE)
+                .map_err(Error::Sdl)?;
+            self.sdl_canvas.string(320, y as i16, &keys.join(", "), COLOR_WHITE)
+                .map_err(Error::Sdl)?;
+        }
+
+        let mouse: String = format!("Mouse look: {}   Sensitivity: {:.2}", if self.input.controls.mouse.look { "on" } else { "off" }, self.input.controls.mouse.sensitivity);
+        self.render_text_centered(top + 40 + (Action::ALL.len() as i32 * 14), &mouse, COLOR_WHITE)?;
+
+        let bottom: i32 = top + 70 + (Action::ALL.len() as i32 * 14);
+        if self.is_waiting_for_key {
+            let prompt: String = format!("Press a key for {}", Action::ALL[self.controls_selection].description());
+            self.render_text_centered(bottom, &prompt, COLOR_WHITE)?;
+        }
+        else {
+            self.render_text_centered(bottom, "Up/Down to pick, Enter to add a key, Backspace to clear", COLOR_WHITE)?;
+            self.render_text_centered(bottom + 14, "Tab to toggle mouse look, Left/Right for sensitivity", COLOR_WHITE)?;
+            self.render_text_centered(bottom + 28, "R to reset the keys, Escape to save and go back", COLOR_WHITE)?;
+        }
+
+        Ok(())
+    }
+
+    fn render_game_over(&mut self) -> error::Result<()> {
+        self.render_overlay(Color::RGBA(128, 0, 0, 160))?;
+        self.render_end_stats("YOU DIED!")
+    }
+
+    fn render_victory(&mut self) -> error::Result<()> {
+        self.render_overlay(Color::RGBA(0, 0, 0, 200))?;
+        self.render_end_stats("YOU WIN!")
+    }
+
+    fn render_end_stats(&mut self, heading: &str) -> error::Result<()> {
+        let y: i32 = self.config.height as i32 / 3;
+        let stats: [String; 3] = [
+            format!("MIPS Knowledge: {}", self.simulation.score),
+            format!("Levels completed: {}/{}", self.simulation.levels_completed, self.simulation.campaign.levels.len()),
+            format!("Survived {:.1} seconds", self.simulation.play_time)
+        ];
+
+        self.render_text_centered(y, heading, COLOR_WHITE)?;
+        for (i, line) in stats.iter().enumerate() {
+            self.render_text_centered(y + 30 + (i as i32 * 10), line, COLOR_WHITE)?;
+        }
+
+        if self.is_entering_name {
+            let prompt: String = format!("New high score! Name: {}_", self.player_name);
+            self.render_text_centered(y + 80, &prompt, COLOR_WHITE)?;
+            self.render_text_centered(y + 100, "Press Enter to save it, or Escape to skip", COLOR_WHITE)?;
+            return Ok(());
+        }
+
+        if let Some(place) = self.high_score_place {
+            let place: String = format!("#{} on the leaderboard", place + 1);
+            self.render_text_centered(y + 60, &place, COLOR_WHITE)?;
+        }
+
+        self.render_text_centered(y + 80, "Press Enter to play again", COLOR_WHITE)?;
+        self.render_text_centered(y + 100, "Press Escape to return to the title screen", COLOR_WHITE)?;
+
+        Ok(())
+    }
+
+    fn render_high_scores(&mut self) -> error::Result<()> {
+        let top: i32 = 60;
+        self.render_text_centered(top, "HIGH SCORES", COLOR_WHITE)?;
+
+        // Every line is padded to the same length, so the columns line up when centered
+        let key: String = self.campaign.leaderboard_key();
+        let mut lines: Vec<String> = vec![format!("    {:<12} {:>5} {:>4} {:>7} {:<16} {:<10}", "Name", "Score", "Diff", "Time", "Map", "Date")];
+        for (i, high_score) in self.high_scores.scores(&key).iter().enumerate() {
+            let map: String = high_score.map.chars().take(16).collect();
+            lines.push(format!("{:>2}. {:<12} {:>5} {:>4} {:>6.1}s {:<16} {:<10}", i + 1, high_score.name, high_score.score, high_score.difficulty, high_score.survival_time, map, high_score.date));
+        }
+
+        if lines.len() == 1 {
+            self.render_text_centered(top + 40, "No high scores yet", COLOR_WHITE)?;
+        }
+        else {
+            for (i, line) in lines.iter().enumerate() {
+                self.render_text_centered(top + 40 + (i as i32 * 14), line, COLOR_WHITE)?;
+            }
+        }
+
+        self.render_text_centered(self.config.height as i32 - 60, "Press Escape to go back", COLOR_WHITE)?;
+
+        Ok(())
+    }
+
+    fn render_saves(&mut self) -> error::Result<()> {
+        if self.is_world_visible() {
+            self.render_overlay(Color::RGBA(0, 0, 0, 200))?;
+        }
+
+        let top: i32 = 100;
+        self.render_text_centered(top, if self.is_saving { "SAVE GAME" } else { "LOAD GAME" }, COLOR_WHITE)?;
+
+        // Padded to the same length, so the slots line up when centered
+        let width: usize = self.save_descriptions.iter().map(|description| description.len()).max().unwrap_or(0);
+        for slot in 0..self.save_descriptions.len() {
+            let marker: &str = if slot == self.save_selection { ">" } else { " " };
+            let line: String = format!("{} Slot {}: {:<width$}", marker, slot + 1, self.save_descriptions[slot], width = width);
+            self.render_text_centered(top + 40 + (slot as i32 * 14), &line, COLOR_WHITE)?;
+        }
+
+        let bottom: i32 = top + 60 + (save::SAVE_SLOTS as i32 * 14);
+        let message: String = self.save_message.clone();
+        self.render_text_centered(bottom, &message, COLOR_WHITE)?;
+        self.render_text_centered(bottom + 30, "Up/Down to pick a slot, Enter to pick it, Escape to go back", COLOR_WHITE)?;
+
+        Ok(())
+    }
+
+    // Whether the level is drawn behind the current screen
+    fn is_world_visible(&self) -> bool {
+        match self.state {
+            GameState::Title | GameState::HighScores => false,
+            GameState::Controls => self.controls_return_state != GameState::Title,
+            GameState::Saves => self.saves_return_state != GameState::Title,
+            _ => true
+        }
+    }
+
+    // Darkens (or tints) whatever has been drawn so far
+    fn render_overlay(&mut self, color: Color) -> error::Result<()> {
+        self.sdl_canvas.set_blend_mode(BlendMode::Blend);
+        self.sdl_canvas.set_draw_color(color);
+        self.sdl_canvas.fill_rect(None)
+            .map_err(Error::Sdl)?;
+        self.sdl_canvas.set_blend_mode(BlendMode::None);
+
+        Ok(())
+    }
+
+    // The built in font is 8x8 pixels
+    fn render_text_centered(&mut self, y: i32, text: &str, color: Color) -> error::Result<()> {
+        let x: i32 = (self.config.width as i32 - (text.len() as i32 * 8)) / 2;
+        self.sdl_canvas.string(x as i16, y as i16, text, color)
+            .map_err(Error::Sdl)
+    }
+}
+
+// The map file a campaign level is loaded from and its legend, unless the map is generated
+fn level_map_paths(level: &Level) -> Vec<String> {
+    match level.map {
+        Some(ref file_path) => vec![file_path.clone(), Legend::path_for_map(Path::new(file_path)).to_string_lossy().into_owned()],
+        None => Vec::new()
+    }
+}
+
+// Enter or Space, or A or Start on a controller
+fn is_confirm(event: &Event) -> bool {
+    matches!(*event,
+        Event::KeyDown { keycode: Some(Keycode::Return), .. } | Event::KeyDown { keycode: Some(Keycode::Space), .. } |
+        Event::ControllerButtonDown { button: Button::A, .. } | Event::ControllerButtonDown { button: Button::Start, .. })
+}
+
+// Escape, or B on a controller
+fn is_back(event: &Event) -> bool {
+    matches!(*event, Event::KeyDown { keycode: Some(Keycode::Escape), .. } | Event::ControllerButtonDown { button: Button::B, .. })
+}
+
+// The signed angle to turn from one rotation to another the short way round
+fn angle_difference(from: f64, to: f64) -> f64 {
+    let difference: f64 = wrap_angle(to - from);
+    if difference > ::std::f64::consts::PI { difference - TWO_PI } else { difference }
+}
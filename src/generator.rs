@@ -0,0 +1,540 @@
+use std::collections::VecDeque;
+use error;
+use error::Error;
+use map::{Map, MapInfo, Position, Spawn, Facing};
+use legend::{Legend, TileLegend, TileFaces, EntityLegend};
+use entity::Archetype;
+use random::Random;
+
+// Builds mazes out of a seed and a handful of settings.
+// The same settings (seed included) always build the same map, so a generated level can be shared
+// by sharing its settings.
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    // Long winding corridors with few branches
+    RecursiveBacktracker,
+    // Lots of short dead ends branching off everywhere
+    Prim,
+    // Open rooms joined up by corridors
+    RoomsAndCorridors
+}
+
+impl Algorithm {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Algorithm::RecursiveBacktracker => "recursive_backtracker",
+            Algorithm::Prim => "prim",
+            Algorithm::RoomsAndCorridors => "rooms_and_corridors"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        match name {
+            "recursive_backtracker" => Some(Algorithm::RecursiveBacktracker),
+            "prim" => Some(Algorithm::Prim),
+            "rooms_and_corridors" => Some(Algorithm::RoomsAndCorridors),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GeneratorSettings {
+    pub seed: u64,
+    pub algorithm: Algorithm,
+    // Even sizes are rounded up to the next odd size, since the maze needs a wall on every edge
+    pub width: u32,
+    pub height: u32,
+    // The chance (0 to 1) of knocking out each wall that separates two corridors, which adds loops
+    pub loop_density: f64,
+    pub room_count: u32,
+    pub treasure_count: u32,
+    // The first chaser is Schindler, the rest are monsters
+    pub monster_count: u32,
+    // How many tiles of walking distance there must be between the spawn and every chaser
+    pub spawn_distance: u32
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> GeneratorSettings {
+        GeneratorSettings {
+            seed: 0,
+            algorithm: Algorithm::RecursiveBacktracker,
+            width: 31,
+            height: 31,
+            loop_density: 0.1,
+            room_count: 3,
+            treasure_count: 30,
+            monster_count: 2,
+            spawn_distance: 12
+        }
+    }
+}
+
+const SYMBOL_WALL: char = '#';
+const SYMBOL_SCHINDLER: char = 'S';
+const SYMBOL_MONSTER: char = 'M';
+const SYMBOL_TREASURE: char = 'T';
+const SYMBOL_EMPTY: char = '.';
+
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+struct Grid {
+    width: u32,
+    height: u32,
+    open: Vec<bool>
+}
+
+impl Grid {
+    fn new(width: u32, height: u32) -> Grid {
+        let mut open: Vec<bool> = Vec::new();
+        open.resize((width * height) as usize, false);
+
+        Grid {
+            width: width,
+            height: height,
+            open: open
+        }
+    }
+
+    // Whether a tile is inside the edge walls
+    fn is_inside(&self, x: i32, y: i32) -> bool {
+        (x >= 1) && (y >= 1) && (x < self.width as i32 - 1) && (y < self.height as i32 - 1)
+    }
+
+    fn is_open(&self, x: i32, y: i32) -> bool {
+        self.is_inside(x, y) && self.open[((y * self.width as i32) + x) as usize]
+    }
+
+    fn set_open(&mut self, x: i32, y: i32) {
+        if self.is_inside(x, y) {
+            self.open[((y * self.width as i32) + x) as usize] = true;
+        }
+    }
+
+    // Maze cells sit on odd coordinates, with walls in between
+    fn random_cell(&self, random: &mut Random) -> (i32, i32) {
+        let x: i32 = (random.range(0, (self.width - 1) / 2) * 2 + 1) as i32;
+        let y: i32 = (random.range(0, (self.height - 1) / 2) * 2 + 1) as i32;
+
+        (x, y)
+    }
+
+    fn open_tiles(&self) -> Vec<(i32, i32)> {
+        let mut tiles: Vec<(i32, i32)> = Vec::new();
+
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                if self.is_open(x, y) {
+                    tiles.push((x, y));
+                }
+            }
+        }
+
+        tiles
+    }
+
+    // Walking distance from the start to every tile (None if it can't be reached)
+    fn distances(&self, start: (i32, i32)) -> Vec<Option<u32>> {
+        let mut distances: Vec<Option<u32>> = Vec::new();
+        distances.resize((self.width * self.height) as usize, None);
+        distances[((start.1 * self.width as i32) + start.0) as usize] = Some(0);
+
+        let mut queue: VecDeque<(i32, i32, u32)> = VecDeque::new();
+        queue.push_back((start.0, start.1, 0));
+
+        while let Some((x, y, distance)) = queue.pop_front() {
+            for &(dx, dy) in DIRECTIONS.iter() {
+                let (nx, ny) = (x + dx, y + dy);
+                let index: usize = ((ny * self.width as i32) + nx) as usize;
+
+                if self.is_open(nx, ny) && distances[index].is_none() {
+                    distances[index] = Some(distance + 1);
+                    queue.push_back((nx, ny, distance + 1));
+                }
+            }
+        }
+
+        distances
+    }
+}
+
+// Carves a maze by walking randomly and backing up at dead ends
+fn carve_recursive_backtracker(grid: &mut Grid, random: &mut Random) {
+    let start: (i32, i32) = grid.random_cell(random);
+    grid.set_open(start.0, start.1);
+
+    let mut stack: Vec<(i32, i32)> = vec![start];
+    while let Some(&(x, y)) = stack.last() {
+        let options: Vec<(i32, i32)> = DIRECTIONS.iter()
+            .cloned()
+            .filter(|&(dx, dy)| grid.is_inside(x + (dx * 2), y + (dy * 2)) && !grid.is_open(x + (dx * 2), y + (dy * 2)))
+            .collect();
+
+        if options.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        let (dx, dy) = options[random.range(0, options.len() as u32) as usize];
+        grid.set_open(x + dx, y + dy);
+        grid.set_open(x + (dx * 2), y + (dy * 2));
+        stack.push((x + (dx * 2), y + (dy * 2)));
+    }
+}
+
+// Carves a maze by growing it out from random walls on its frontier (randomized Prim's)
+fn carve_prim(grid: &mut Grid, random: &mut Random) {
+    // (wall x, wall y, cell x, cell y)
+    let mut frontier: Vec<(i32, i32, i32, i32)> = Vec::new();
+
+    let start: (i32, i32) = grid.random_cell(random);
+    grid.set_open(start.0, start.1);
+    add_frontier(grid, &mut frontier, start);
+
+    while !frontier.is_empty() {
+        let index: usize = random.range(0, frontier.len() as u32) as usize;
+        let (wall_x, wall_y, cell_x, cell_y) = frontier.swap_remove(index);
+
+        if grid.is_open(cell_x, cell_y) {
+            continue;
+        }
+
+        grid.set_open(wall_x, wall_y);
+        grid.set_open(cell_x, cell_y);
+        add_frontier(grid, &mut frontier, (cell_x, cell_y));
+    }
+}
+
+fn add_frontier(grid: &Grid, frontier: &mut Vec<(i32, i32, i32, i32)>, cell: (i32, i32)) {
+    for &(dx, dy) in DIRECTIONS.iter() {
+        let (cell_x, cell_y) = (cell.0 + (dx * 2), cell.1 + (dy * 2));
+
+        if grid.is_inside(cell_x, cell_y) && !grid.is_open(cell_x, cell_y) {
+            frontier.push((cell.0 + dx, cell.1 + dy, cell_x, cell_y));
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Room {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32
+}
+
+impl Room {
+    fn center(&self) -> (i32, i32) {
+        (self.x + (self.width / 2), self.y + (self.height / 2))
+    }
+
+    // Whether the rooms overlap or touch
+    fn is_near(&self, other: &Room) -> bool {
+        (self.x - 1 <= other.x + other.width) && (other.x - 1 <= self.x + self.width) &&
+        (self.y - 1 <= other.y + other.height) && (other.y - 1 <= self.y + self.height)
+    }
+}
+
+// Places up to room_count rooms that don't overlap, on odd coordinates so they line up with maze cells
+fn place_rooms(grid: &Grid, random: &mut Random, room_count: u32) -> Vec<Room> {
+    let mut rooms: Vec<Room> = Vec::new();
+    let max_size: u32 = 7.min(grid.width - 2).min(grid.height - 2);
+
+    for _ in 0..(room_count * 20) {
+        if rooms.len() as u32 >= room_count {
+            break;
+        }
+
+        // Odd sizes from 3 up to max_size
+        let width: i32 = (random.range(1, max_size.div_ceil(2)) * 2 + 1) as i32;
+        let height: i32 = (random.range(1, max_size.div_ceil(2)) * 2 + 1) as i32;
+        let x: i32 = (random.range(0, ((grid.width as i32 - width) / 2) as u32) * 2 + 1) as i32;
+        let y: i32 = (random.range(0, ((grid.height as i32 - height) / 2) as u32) * 2 + 1) as i32;
+
+        let room = Room {
+            x: x,
+            y: y,
+            width: width,
+            height: height
+        };
+
+        if grid.is_inside(x + width - 1, y + height - 1) && !rooms.iter().any(|other| room.is_near(other)) {
+            rooms.push(room);
+        }
+    }
+
+    rooms
+}
+
+fn carve_room(grid: &mut Grid, room: &Room) {
+    for y in room.y..(room.y + room.height) {
+        for x in room.x..(room.x + room.width) {
+            grid.set_open(x, y);
+        }
+    }
+}
+
+// Joins each room to the one before it with an L shaped corridor
+fn carve_corridors(grid: &mut Grid, random: &mut Random, rooms: &[Room]) {
+    for pair in rooms.windows(2) {
+        let (from_x, from_y) = pair[0].center();
+        let (to_x, to_y) = pair[1].center();
+        let corner: (i32, i32) = if random.chance(0.5) { (to_x, from_y) } else { (from_x, to_y) };
+
+        carve_line(grid, (from_x, from_y), corner);
+        carve_line(grid, corner, (to_x, to_y));
+    }
+}
+
+fn carve_line(grid: &mut Grid, from: (i32, i32), to: (i32, i32)) {
+    let (mut x, mut y) = from;
+    grid.set_open(x, y);
+
+    while (x, y) != to {
+        x += (to.0 - x).signum();
+        if x == to.0 {
+            y += (to.1 - y).signum();
+        }
+
+        grid.set_open(x, y);
+    }
+}
+
+// Knocks out walls that sit between two open tiles in a line
+fn carve_loops(grid: &mut Grid, random: &mut Random, loop_density: f64) {
+    if loop_density <= 0.0 {
+        return;
+    }
+
+    for y in 1..(grid.height as i32 - 1) {
+        for x in 1..(grid.width as i32 - 1) {
+            if grid.is_open(x, y) {
+                continue;
+            }
+
+            let is_horizontal_gap: bool = grid.is_open(x - 1, y) && grid.is_open(x + 1, y) && !grid.is_open(x, y - 1) && !grid.is_open(x, y + 1);
+            let is_vertical_gap: bool = grid.is_open(x, y - 1) && grid.is_open(x, y + 1) && !grid.is_open(x - 1, y) && !grid.is_open(x + 1, y);
+
+            if (is_horizontal_gap || is_vertical_gap) && random.chance(loop_density) {
+                grid.set_open(x, y);
+            }
+        }
+    }
+}
+
+fn invalid_settings(message: String) -> Error {
+    Error::InvalidMap(format!("Can't generate map: {}", message))
+}
+
+// The legend generated maps are saved with
+fn generated_legend() -> Legend {
+    let entity = |color: [u8; 3], symbol: char, archetype: Archetype, texture: &str| EntityLegend {
+        color: color,
+        symbol: symbol,
+        archetype: archetype,
+        texture: texture.to_string(),
+        solid: false
+    };
+
+    Legend {
+        map: MapInfo::default(),
+        tile: vec![TileLegend {
+            color: [0, 0, 0],
+            symbol: SYMBOL_WALL,
+            name: "wall".to_string(),
+            texture: "wall".to_string(),
+            solid: true,
+            faces: TileFaces::default()
+        }],
+        entity: vec![
+            entity([255, 0, 0], SYMBOL_SCHINDLER, Archetype::Schindler, "schindler"),
+            entity([0, 0, 255], SYMBOL_MONSTER, Archetype::Monster, "monster"),
+            entity([0, 255, 0], SYMBOL_TREASURE, Archetype::Treasure, "treasure")
+        ],
+        spawn: Vec::new()
+    }
+}
+
+// Builds a map from the settings. Every treasure is guaranteed to be reachable from the spawn.
+pub fn generate(settings: &GeneratorSettings) -> error::Result<Map> {
+    let width: u32 = settings.width | 1;
+    let height: u32 = settings.height | 1;
+    if (width < 5) || (height < 5) {
+        return Err(invalid_settings(format!("{}x{} is too small, maps need to be at least 5x5", width, height)));
+    }
+
+    let mut random: Random = Random::new(settings.seed);
+    let mut grid: Grid = Grid::new(width, height);
+
+    let rooms: Vec<Room> = place_rooms(&grid, &mut random, settings.room_count);
+
+    match settings.algorithm {
+        Algorithm::RecursiveBacktracker => carve_recursive_backtracker(&mut grid, &mut random),
+        Algorithm::Prim => carve_prim(&mut grid, &mut random),
+        Algorithm::RoomsAndCorridors => {
+            if rooms.is_empty() {
+                return Err(invalid_settings("rooms and corridors needs at least one room".to_string()));
+            }
+
+            carve_corridors(&mut grid, &mut random, &rooms);
+        }
+    }
+
+    for room in rooms.iter() {
+        carve_room(&mut grid, room);
+    }
+
+    carve_loops(&mut grid, &mut random, settings.loop_density);
+
+    // Spawn, facing the first open direction
+    let open_tiles: Vec<(i32, i32)> = grid.open_tiles();
+    let spawn: (i32, i32) = open_tiles[random.range(0, open_tiles.len() as u32) as usize];
+    let facing: Facing = [Facing::North, Facing::East, Facing::South, Facing::West].iter()
+        .zip(DIRECTIONS.iter())
+        .find(|&(_, &(dx, dy))| grid.is_open(spawn.0 + dx, spawn.1 + dy))
+        .map_or(Facing::East, |(facing, _)| *facing);
+
+    // Chasers go far enough away from the spawn, treasure goes anywhere else that can be reached
+    let distances: Vec<Option<u32>> = grid.distances(spawn);
+    let distance_to = |tile: &(i32, i32)| distances[((tile.1 * width as i32) + tile.0) as usize];
+
+    let mut chaser_tiles: Vec<(i32, i32)> = open_tiles.iter()
+        .cloned()
+        .filter(|tile| distance_to(tile).map_or(false, |distance| distance >= settings.spawn_distance))
+        .collect();
+    random.shuffle(&mut chaser_tiles);
+
+    if (chaser_tiles.len() as u32) < settings.monster_count {
+        return Err(invalid_settings(format!("there's only room for {} of the {} monsters at least {} tiles from the spawn", chaser_tiles.len(), settings.monster_count, settings.spawn_distance)));
+    }
+    chaser_tiles.truncate(settings.monster_count as usize);
+
+    let mut treasure_tiles: Vec<(i32, i32)> = open_tiles.iter()
+        .cloned()
+        .filter(|tile| (*tile != spawn) && distance_to(tile).is_some() && !chaser_tiles.contains(tile))
+        .collect();
+    random.shuffle(&mut treasure_tiles);
+
+    if (treasure_tiles.len() as u32) < settings.treasure_count {
+        return Err(invalid_settings(format!("there's only room for {} of the {} treasures", treasure_tiles.len(), settings.treasure_count)));
+    }
+    treasure_tiles.truncate(settings.treasure_count as usize);
+
+    // Lay the whole thing out as symbols, and build it the same way text maps are
+    let mut symbols: Vec<char> = grid.open.iter().map(|&open| if open { SYMBOL_EMPTY } else { SYMBOL_WALL }).collect();
+    for (index, tile) in chaser_tiles.iter().enumerate() {
+        symbols[((tile.1 * width as i32) + tile.0) as usize] = if index == 0 { SYMBOL_SCHINDLER } else { SYMBOL_MONSTER };
+    }
+    for tile in treasure_tiles.iter() {
+        symbols[((tile.1 * width as i32) + tile.0) as usize] = SYMBOL_TREASURE;
+    }
+
+    let info = MapInfo {
+        name: format!("{} #{}", settings.algorithm.name(), settings.seed),
+        music: None,
+        spawn: Some(Spawn::new(spawn.0 as u32, spawn.1 as u32, facing))
+    };
+
+    let map: Map = Map::build(width, height, generated_legend(), info, |legend, x, y| {
+        let symbol: char = symbols[((y * width) + x) as usize];
+        if symbol == SYMBOL_EMPTY {
+            return Ok(None);
+        }
+
+        Ok(legend.find_symbol(symbol))
+    })?;
+
+    check_treasure_reachable(&map)?;
+
+    Ok(map)
+}
+
+// Makes sure the chasers' pathfinding can reach every treasure from the spawn
+fn check_treasure_reachable(map: &Map) -> error::Result<()> {
+    let start: Position = Position::new(map.spawn.x as i32, map.spawn.y as i32);
+
+    let mut is_reached: Vec<bool> = Vec::new();
+    is_reached.resize((map.width * map.height) as usize, false);
+    is_reached[start.to_index(map.width as usize)] = true;
+
+    let mut queue: VecDeque<Position> = VecDeque::new();
+    queue.push_back(start);
+
+    while let Some(pos) = queue.pop_front() {
+        for (neighbor, _) in map.get_neighbors(pos) {
+            let index: usize = neighbor.to_index(map.width as usize);
+            if !is_reached[index] {
+                is_reached[index] = true;
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    for ent in map.entities.iter() {
+        if (ent.archetype == Archetype::Treasure) && !is_reached[Position::new(ent.x as i32, ent.y as i32).to_index(map.width as usize)] {
+            return Err(invalid_settings(format!("the treasure at {}, {} can't be reached", ent.x, ent.y)));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use map::Map;
+    use super::{generate, check_treasure_reachable, Algorithm, GeneratorSettings};
+
+    const ALGORITHMS: [Algorithm; 3] = [Algorithm::RecursiveBacktracker, Algorithm::Prim, Algorithm::RoomsAndCorridors];
+
+    fn settings(algorithm: Algorithm, seed: u64) -> GeneratorSettings {
+        GeneratorSettings {
+            seed: seed,
+            algorithm: algorithm,
+            ..GeneratorSettings::default()
+        }
+    }
+
+    // The map as symbols, row by row, followed by the spawn
+    fn layout(map: &Map) -> String {
+        let mut layout: String = String::new();
+
+        for y in 0..map.height {
+            for x in 0..map.width {
+                layout.push(map.get_legend_entry(x, y).unwrap().map_or('.', |entry| entry.symbol()));
+            }
+
+            layout.push('\n');
+        }
+
+        layout.push_str(&format!("{} {} {}", map.spawn.x, map.spawn.y, map.spawn.facing.name()));
+        layout
+    }
+
+    #[test]
+    fn same_seed_builds_same_map() {
+        for &algorithm in ALGORITHMS.iter() {
+            for seed in 0..5 {
+                let first: Map = generate(&settings(algorithm, seed)).unwrap();
+                let second: Map = generate(&settings(algorithm, seed)).unwrap();
+
+                assert_eq!(layout(&first), layout(&second), "{} #{} built two different maps", algorithm.name(), seed);
+            }
+        }
+    }
+
+    #[test]
+    fn every_treasure_is_reachable() {
+        for &algorithm in ALGORITHMS.iter() {
+            for seed in 0..20 {
+                let map: Map = generate(&settings(algorithm, seed)).unwrap();
+
+                assert_eq!(map.count_treasure(), GeneratorSettings::default().treasure_count);
+                assert!(check_treasure_reachable(&map).is_ok(), "{} #{} has treasure that can't be reached", algorithm.name(), seed);
+            }
+        }
+    }
+}
@@ -0,0 +1,489 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use sdl2::controller::{Axis, Button};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use toml;
+use error;
+use error::Error;
+
+// Everything the player can do with a key.
+// Menus still use the arrow keys, Enter and Escape on top of these.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    TurnLeft,
+    TurnRight,
+    StrafeLeft,
+    StrafeRight,
+    Pause,
+    VolumeDown,
+    VolumeUp,
+    Mute,
+    ToggleMouseLook
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::Pause,
+        Action::VolumeDown,
+        Action::VolumeUp,
+        Action::Mute,
+        Action::ToggleMouseLook
+    ];
+
+    // The name used in the controls file
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::Pause => "pause",
+            Action::VolumeDown => "volume_down",
+            Action::VolumeUp => "volume_up",
+            Action::Mute => "mute",
+            Action::ToggleMouseLook => "toggle_mouse_look"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().cloned().find(|action| action.name() == name)
+    }
+
+    // The game controller button that triggers the action, if any
+    pub fn for_button(button: Button) -> Option<Action> {
+        match button {
+            Button::DPadUp => Some(Action::MoveForward),
+            Button::DPadDown => Some(Action::MoveBackward),
+            Button::DPadLeft => Some(Action::TurnLeft),
+            Button::DPadRight => Some(Action::TurnRight),
+            Button::LeftShoulder => Some(Action::StrafeLeft),
+            Button::RightShoulder => Some(Action::StrafeRight),
+            Button::Start => Some(Action::Pause),
+            _ => None
+        }
+    }
+
+    // The name shown on the controls screen
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBackward => "Move backward",
+            Action::TurnLeft => "Turn left",
+            Action::TurnRight => "Turn right",
+            Action::StrafeLeft => "Strafe left",
+            Action::StrafeRight => "Strafe right",
+            Action::Pause => "Pause",
+            Action::VolumeDown => "Volume down",
+            Action::VolumeUp => "Volume up",
+            Action::Mute => "Mute",
+            Action::ToggleMouseLook => "Toggle mouse look"
+        }
+    }
+}
+
+// Which keys trigger each action. Any number of keys can be bound to an action.
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<Keycode>)>
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            bindings: vec![
+                (Action::MoveForward, vec![Keycode::W, Keycode::Up]),
+                (Action::MoveBackward, vec![Keycode::S, Keycode::Down]),
+                (Action::TurnLeft, vec![Keycode::A, Keycode::Left]),
+                (Action::TurnRight, vec![Keycode::D, Keycode::Right]),
+                (Action::StrafeLeft, vec![Keycode::Q]),
+                (Action::StrafeRight, vec![Keycode::E]),
+                (Action::Pause, vec![Keycode::Escape, Keycode::P]),
+                (Action::VolumeDown, vec![Keycode::Minus, Keycode::KpMinus]),
+                (Action::VolumeUp, vec![Keycode::Equals, Keycode::KpPlus]),
+                (Action::Mute, vec![Keycode::M]),
+                (Action::ToggleMouseLook, vec![Keycode::L])
+            ]
+        }
+    }
+}
+
+impl KeyBindings {
+    // Actions the table leaves out keep their default keys
+    fn from_names(table: &BTreeMap<String, Vec<String>>) -> Result<KeyBindings, String> {
+        let mut key_bindings: KeyBindings = KeyBindings::default();
+        for (action_name, key_names) in table.iter() {
+            let action: Action = Action::from_name(action_name)
+                .ok_or_else(|| format!("unknown action '{}'", action_name))?;
+
+            let mut keys: Vec<Keycode> = Vec::new();
+            for key_name in key_names.iter() {
+                keys.push(Keycode::from_name(key_name).ok_or_else(|| format!("unknown key '{}' for {}", key_name, action_name))?);
+            }
+
+            key_bindings.set_keys(action, keys);
+        }
+
+        Ok(key_bindings)
+    }
+
+    fn to_names(&self) -> BTreeMap<String, Vec<String>> {
+        self.bindings.iter()
+            .map(|&(action, ref keys)| (action.name().to_string(), keys.iter().map(|key| key.name()).collect()))
+            .collect()
+    }
+
+    pub fn keys(&self, action: Action) -> &[Keycode] {
+        self.bindings.iter()
+            .find(|&&(bound_action, _)| bound_action == action)
+            .map_or(&[], |&(_, ref keys)| keys.as_slice())
+    }
+
+    pub fn set_keys(&mut self, action: Action, keys: Vec<Keycode>) {
+        match self.bindings.iter_mut().find(|&&mut (bound_action, _)| bound_action == action) {
+            Some(binding) => binding.1 = keys,
+            None => self.bindings.push((action, keys))
+        }
+    }
+
+    // Binds another key to the action, taking it away from whatever action had it before
+    pub fn add_key(&mut self, action: Action, key: Keycode) {
+        for &mut (bound_action, ref mut keys) in self.bindings.iter_mut() {
+            if bound_action != action {
+                keys.retain(|&bound_key| bound_key != key);
+            }
+        }
+
+        let mut keys: Vec<Keycode> = self.keys(action).to_vec();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+
+        self.set_keys(action, keys);
+    }
+
+    pub fn actions_for(&self, key: Keycode) -> Vec<Action> {
+        self.bindings.iter()
+            .filter(|&&(_, ref keys)| keys.contains(&key))
+            .map(|&(action, _)| action)
+            .collect()
+    }
+}
+
+// Turning with the mouse instead of the turn keys, which strafe instead
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MouseSettings {
+    pub look: bool,
+    // Degrees turned per pixel of mouse motion
+    pub sensitivity: f64,
+    // From 0 (none) to just under 1 (very smooth)
+    pub smoothing: f64
+}
+
+impl Default for MouseSettings {
+    fn default() -> MouseSettings {
+        MouseSettings {
+            look: false,
+            sensitivity: 0.2,
+            smoothing: 0.0
+        }
+    }
+}
+
+// The sticks on game controllers: the left one moves and strafes, the right one turns
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ControllerSettings {
+    // How far (from 0 to 1) a stick has to be pushed before it does anything
+    pub dead_zone: f64,
+    // Degrees per second when the stick is pushed all the way
+    pub turn_speed: f64
+}
+
+impl Default for ControllerSettings {
+    fn default() -> ControllerSettings {
+        ControllerSettings {
+            dead_zone: 0.2,
+            turn_speed: 180.0
+        }
+    }
+}
+
+// Everything about how the player controls the game, saved in a TOML file:
+//
+// [keys]
+// move_forward = ["W", "Up"]
+//
+// [mouse]
+// look = true
+// sensitivity = 0.2
+//
+// [controller]
+// dead_zone = 0.2
+#[derive(Clone, Debug, Default)]
+pub struct Controls {
+    pub keys: KeyBindings,
+    pub mouse: MouseSettings,
+    pub controller: ControllerSettings
+}
+
+#[derive(Deserialize, Serialize)]
+struct ControlsFile {
+    #[serde(default)]
+    keys: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    mouse: MouseSettings,
+    #[serde(default)]
+    controller: ControllerSettings
+}
+
+impl Controls {
+    pub fn load(file_path: &Path) -> error::Result<Controls> {
+        let mut contents: String = String::new();
+        File::open(file_path)?.read_to_string(&mut contents)?;
+
+        let invalid = |message: String| Error::Config(format!("Invalid controls {}: {}", file_path.display(), message));
+
+        let file: ControlsFile = toml::from_str(&contents)
+            .map_err(|e| invalid(e.to_string()))?;
+
+        let mut mouse: MouseSettings = file.mouse;
+        mouse.smoothing = mouse.smoothing.clamp(0.0, 0.99);
+
+        let mut controller: ControllerSettings = file.controller;
+        controller.dead_zone = controller.dead_zone.clamp(0.0, 0.99);
+
+        Ok(Controls {
+            keys: KeyBindings::from_names(&file.keys).map_err(invalid)?,
+            mouse: mouse,
+            controller: controller
+        })
+    }
+
+    // Loads the controls, falling back to the defaults if there's no file yet
+    pub fn load_or_default(file_path: &Path) -> error::Result<Controls> {
+        if !file_path.exists() {
+            return Ok(Controls::default());
+        }
+
+        Controls::load(file_path)
+    }
+
+    pub fn save(&self, file_path: &Path) -> io::Result<()> {
+        let file = ControlsFile {
+            keys: self.keys.to_names(),
+            mouse: self.mouse,
+            controller: self.controller
+        };
+
+        let contents: String = toml::to_string(&file)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to write controls {}: {}", file_path.display(), e)))?;
+
+        File::create(file_path)?.write_all(contents.as_bytes())
+    }
+}
+
+// The buttons held down and sticks pushed on one game controller
+#[derive(Default)]
+struct PadState {
+    held_buttons: HashSet<Button>,
+    // (x, y) from -1 to 1, before the dead zone
+    left_stick: (f64, f64),
+    right_stick: (f64, f64)
+}
+
+// Tracks which actions are held down, how far the sticks are pushed,
+// and how far the mouse has turned the player
+pub struct Input {
+    pub controls: Controls,
+    held_keys: HashSet<Keycode>,
+    // Keyed by each controller's instance id, so a controller's input goes away with it
+    pads: HashMap<i32, PadState>,
+    // Mouse motion since the last turn was taken, in pixels
+    mouse_motion: i32,
+    mouse_turn: f64
+}
+
+impl Input {
+    pub fn new(controls: Controls) -> Input {
+        Input {
+            controls: controls,
+            held_keys: HashSet::new(),
+            pads: HashMap::new(),
+            mouse_motion: 0,
+            mouse_turn: 0.0
+        }
+    }
+
+    // Returns the actions that were just pressed by the event, if any
+    pub fn handle_event(&mut self, event: &Event) -> Vec<Action> {
+        match *event {
+            Event::KeyDown { keycode: Some(key), repeat: false, .. } => {
+                self.held_keys.insert(key);
+                self.controls.keys.actions_for(key)
+            },
+            Event::KeyUp { keycode: Some(key), .. } => {
+                self.held_keys.remove(&key);
+                Vec::new()
+            },
+            Event::MouseMotion { xrel, .. } if self.controls.mouse.look => {
+                self.mouse_motion += xrel;
+                Vec::new()
+            },
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.pads.entry(which).or_default().held_buttons.insert(button);
+                Action::for_button(button).into_iter().collect()
+            },
+            Event::ControllerButtonUp { which, button, .. } => {
+                if let Some(pad) = self.pads.get_mut(&which) {
+                    pad.held_buttons.remove(&button);
+                }
+                Vec::new()
+            },
+            Event::ControllerAxisMotion { which, axis, value, .. } => {
+                let pad: &mut PadState = self.pads.entry(which).or_default();
+                let value: f64 = (value as f64 / 32767.0).clamp(-1.0, 1.0);
+                match axis {
+                    Axis::LeftX => pad.left_stick.0 = value,
+                    Axis::LeftY => pad.left_stick.1 = value,
+                    Axis::RightX => pad.right_stick.0 = value,
+                    Axis::RightY => pad.right_stick.1 = value,
+                    _ => {}
+                }
+                Vec::new()
+            },
+            // Whatever a controller was holding down is let go when it's pulled out
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.pads.remove(&which);
+                Vec::new()
+            },
+
+            _ => Vec::new()
+        }
+    }
+
+    // Whether a key or controller button for the action is held down
+    pub fn is_held(&self, action: Action) -> bool {
+        self.controls.keys.keys(action).iter().any(|key| self.held_keys.contains(key)) ||
+        self.pads.values().any(|pad| pad.held_buttons.iter().any(|&button| Action::for_button(button) == Some(action)))
+    }
+
+    // How far (from 0 to 1) the sticks are pushed toward the action, on whichever controller pushes furthest
+    pub fn stick_amount(&self, action: Action) -> f64 {
+        self.pads.values().map(|pad| self.pad_stick_amount(pad, action)).fold(0.0, f64::max)
+    }
+
+    fn pad_stick_amount(&self, pad: &PadState, action: Action) -> f64 {
+        let left: (f64, f64) = self.apply_dead_zone(pad.left_stick);
+        let right: (f64, f64) = self.apply_dead_zone(pad.right_stick);
+
+        let amount: f64 = match action {
+            Action::MoveForward => -left.1,
+            Action::MoveBackward => left.1,
+            Action::StrafeLeft => -left.0,
+            Action::StrafeRight => left.0,
+            Action::TurnLeft => -right.0,
+            Action::TurnRight => right.0,
+            _ => 0.0
+        };
+
+        amount.max(0.0)
+    }
+
+    // How far (from 0 to 1) the action is pushed, by either a button or a stick
+    pub fn amount(&self, action: Action) -> f64 {
+        if self.is_held(action) { 1.0 } else { self.stick_amount(action) }
+    }
+
+    // Sticks never rest exactly at the center, so anything inside the dead zone counts as zero,
+    // and the rest is stretched back out to cover 0 to 1
+    fn apply_dead_zone(&self, stick: (f64, f64)) -> (f64, f64) {
+        let dead_zone: f64 = self.controls.controller.dead_zone;
+        let length: f64 = ((stick.0 * stick.0) + (stick.1 * stick.1)).sqrt();
+        if length <= dead_zone {
+            return (0.0, 0.0);
+        }
+
+        let scale: f64 = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0) / length;
+        (stick.0 * scale, stick.1 * scale)
+    }
+
+    // How far to turn (in radians, clockwise) from the mouse motion since the last call
+    pub fn take_mouse_turn(&mut self) -> f64 {
+        let turn: f64 = (self.mouse_motion as f64 * self.controls.mouse.sensitivity).to_radians();
+        let smoothing: f64 = self.controls.mouse.smoothing;
+        self.mouse_motion = 0;
+
+        self.mouse_turn = (self.mouse_turn * smoothing) + (turn * (1.0 - smoothing));
+        self.mouse_turn
+    }
+
+    // Forgets every held key and any mouse motion, e.g. when the game stops listening for a while
+    pub fn clear(&mut self) {
+        self.held_keys.clear();
+        for pad in self.pads.values_mut() {
+            pad.held_buttons.clear();
+        }
+        self.mouse_motion = 0;
+        self.mouse_turn = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::controller::{Axis, Button};
+    use sdl2::event::Event;
+    use super::{Action, Controls, Input};
+
+    fn push_stick(input: &mut Input, which: i32, axis: Axis, value: i16) {
+        input.handle_event(&Event::ControllerAxisMotion { timestamp: 0, which: which, axis: axis, value: value });
+    }
+
+    fn unplug(input: &mut Input, which: i32) {
+        input.handle_event(&Event::ControllerDeviceRemoved { timestamp: 0, which: which });
+    }
+
+    #[test]
+    fn unplugging_lets_go_of_sticks_and_buttons() {
+        let mut input: Input = Input::new(Controls::default());
+        push_stick(&mut input, 0, Axis::LeftY, -32767);
+        push_stick(&mut input, 0, Axis::RightX, 32767);
+        input.handle_event(&Event::ControllerButtonDown { timestamp: 0, which: 0, button: Button::LeftShoulder });
+
+        assert!(input.amount(Action::MoveForward) > 0.9);
+        assert!(input.amount(Action::TurnRight) > 0.9);
+        assert!(input.is_held(Action::StrafeLeft));
+
+        unplug(&mut input, 0);
+
+        assert_eq!(input.amount(Action::MoveForward), 0.0);
+        assert_eq!(input.amount(Action::TurnRight), 0.0);
+        assert!(!input.is_held(Action::StrafeLeft));
+    }
+
+    #[test]
+    fn unplugging_one_controller_keeps_the_others() {
+        let mut input: Input = Input::new(Controls::default());
+        push_stick(&mut input, 0, Axis::LeftY, -32767);
+        push_stick(&mut input, 1, Axis::RightX, 32767);
+
+        unplug(&mut input, 0);
+
+        assert_eq!(input.amount(Action::MoveForward), 0.0);
+        assert!(input.amount(Action::TurnRight) > 0.9);
+    }
+}
@@ -0,0 +1,333 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use sdl2::pixels::Color;
+use toml;
+use entity::Archetype;
+use map::{MapInfo, Facing};
+use text_map::EMPTY_SYMBOL;
+use error;
+use error::Error;
+
+// Describes what each pixel color in a map image (or character in a text map) turns into.
+// Every map has a legend next to it with the same name, e.g. level1.png and level1.toml.
+// Since images can't hold anything else, the legend also holds the map info for image maps.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Legend {
+    #[serde(default)]
+    pub map: MapInfo,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tile: Vec<TileLegend>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entity: Vec<EntityLegend>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spawn: Vec<SpawnLegend>
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TileLegend {
+    pub color: [u8; 3],
+    pub symbol: char,
+    pub name: String,
+    pub texture: String,
+    #[serde(default = "default_solid")]
+    pub solid: bool,
+    // Textures for some faces of the tile instead of texture
+    #[serde(default, skip_serializing_if = "TileFaces::is_empty")]
+    pub faces: TileFaces
+}
+
+// Textures for each face of a tile. North is the face at the top of the map (lowest y).
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TileFaces {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub north: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub east: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub south: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub west: Option<String>
+}
+
+impl TileFaces {
+    pub fn is_empty(&self) -> bool {
+        self.north.is_none() && self.east.is_none() && self.south.is_none() && self.west.is_none()
+    }
+
+    // The texture of each face, north, east, south then west, using texture for faces that don't have their own
+    pub fn textures<'a>(&'a self, texture: &'a str) -> [&'a str; 4] {
+        let face = |name: &'a Option<String>| name.as_ref().map_or(texture, |name| name.as_str());
+        [face(&self.north), face(&self.east), face(&self.south), face(&self.west)]
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EntityLegend {
+    pub color: [u8; 3],
+    pub symbol: char,
+    pub archetype: Archetype,
+    pub texture: String,
+    #[serde(default)]
+    pub solid: bool
+}
+
+// Marks where the player starts, and which way they face
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SpawnLegend {
+    pub color: [u8; 3],
+    pub symbol: char,
+    pub facing: Facing
+}
+
+#[derive(Copy, Clone)]
+pub enum LegendEntry<'a> {
+    Tile(&'a TileLegend),
+    Entity(&'a EntityLegend),
+    Spawn(&'a SpawnLegend)
+}
+
+fn default_solid() -> bool {
+    true
+}
+
+impl Legend {
+    pub fn load(file_path: &Path) -> error::Result<Legend> {
+        let mut contents: String = String::new();
+        File::open(file_path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| Error::missing_asset(&file_path.to_string_lossy(), e))?;
+
+        let legend: Legend = Legend::parse(&contents)
+            .map_err(|e| Error::InvalidMap(format!("Invalid map legend {}: {}", file_path.display(), e)))?;
+
+        legend.validate()
+            .map_err(|message| Error::InvalidMap(format!("Invalid map legend {}: {}", file_path.display(), message)))?;
+
+        Ok(legend)
+    }
+
+    pub fn parse(contents: &str) -> Result<Legend, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    pub fn save(&self, file_path: &Path) -> io::Result<()> {
+        let contents: String = toml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to write map legend {}: {}", file_path.display(), e)))?;
+
+        File::create(file_path)?.write_all(contents.as_bytes())
+    }
+
+    // Every color and symbol has to mean one thing, and every tile, entity and spawn has to be told
+    // apart by what's placed on the map, or maps wouldn't convert between formats and back unchanged
+    pub fn validate(&self) -> Result<(), String> {
+        let entries: Vec<LegendEntry> = self.entries();
+
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.symbol() == EMPTY_SYMBOL {
+                return Err(format!("'{}' is empty floor, so it can't be used as the symbol for {}", EMPTY_SYMBOL, entry.describe()));
+            }
+
+            for other in entries[(i + 1)..].iter() {
+                if entry.color() == other.color() {
+                    let color: Color = entry.color();
+                    return Err(format!("{} and {} both use the color [{}, {}, {}]", entry.describe(), other.describe(), color.r, color.g, color.b));
+                }
+
+                if entry.symbol() == other.symbol() {
+                    return Err(format!("{} and {} both use the symbol '{}'", entry.describe(), other.describe(), entry.symbol()));
+                }
+
+                if entry.places_same_as(other) {
+                    return Err(format!("{} and {} place the same thing on the map, so they can't be told apart", entry.describe(), other.describe()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Every tile, entity and spawn entry, in that order
+    pub fn entries<'a>(&'a self) -> Vec<LegendEntry<'a>> {
+        self.tile.iter().map(LegendEntry::Tile)
+            .chain(self.entity.iter().map(LegendEntry::Entity))
+            .chain(self.spawn.iter().map(LegendEntry::Spawn))
+            .collect()
+    }
+
+    // The legend file that belongs to the given map image
+    pub fn path_for_map(map_path: &Path) -> PathBuf {
+        map_path.with_extension("toml")
+    }
+
+    // Finds the entry for a pixel color. Only fully opaque pixels have meaning.
+    pub fn find<'a>(&'a self, color: Color) -> Option<LegendEntry<'a>> {
+        if color.a != 255 {
+            return None;
+        }
+
+        let rgb: [u8; 3] = [color.r, color.g, color.b];
+
+        if let Some(tile) = self.tile.iter().find(|tile| tile.color == rgb) {
+            return Some(LegendEntry::Tile(tile));
+        }
+
+        if let Some(entity) = self.entity.iter().find(|entity| entity.color == rgb) {
+            return Some(LegendEntry::Entity(entity));
+        }
+
+        if let Some(spawn) = self.spawn.iter().find(|spawn| spawn.color == rgb) {
+            return Some(LegendEntry::Spawn(spawn));
+        }
+
+        None
+    }
+
+    // Finds the entry for a character in a text map
+    pub fn find_symbol<'a>(&'a self, symbol: char) -> Option<LegendEntry<'a>> {
+        if let Some(tile) = self.tile.iter().find(|tile| tile.symbol == symbol) {
+            return Some(LegendEntry::Tile(tile));
+        }
+
+        if let Some(entity) = self.entity.iter().find(|entity| entity.symbol == symbol) {
+            return Some(LegendEntry::Entity(entity));
+        }
+
+        if let Some(spawn) = self.spawn.iter().find(|spawn| spawn.symbol == symbol) {
+            return Some(LegendEntry::Spawn(spawn));
+        }
+
+        None
+    }
+}
+
+impl<'a> LegendEntry<'a> {
+    pub fn color(&self) -> Color {
+        let color: [u8; 3] = match *self {
+            LegendEntry::Tile(tile) => tile.color,
+            LegendEntry::Entity(entity) => entity.color,
+            LegendEntry::Spawn(spawn) => spawn.color
+        };
+
+        Color {r: color[0], g: color[1], b: color[2], a: 255}
+    }
+
+    pub fn symbol(&self) -> char {
+        match *self {
+            LegendEntry::Tile(tile) => tile.symbol,
+            LegendEntry::Entity(entity) => entity.symbol,
+            LegendEntry::Spawn(spawn) => spawn.symbol
+        }
+    }
+
+    // Names the entry in error messages
+    fn describe(&self) -> String {
+        match *self {
+            LegendEntry::Tile(tile) => format!("tile '{}'", tile.name),
+            LegendEntry::Entity(entity) => format!("{:?} entity '{}'", entity.archetype, entity.texture),
+            LegendEntry::Spawn(spawn) => format!("spawn facing {}", spawn.facing.name())
+        }
+    }
+
+    // Whether both entries turn into the same tile, entity or spawn once the map is built
+    fn places_same_as(&self, other: &LegendEntry) -> bool {
+        match (*self, *other) {
+            (LegendEntry::Tile(a), LegendEntry::Tile(b)) => {
+                (a.faces.textures(&a.texture) == b.faces.textures(&b.texture)) && (a.texture == b.texture) && (a.solid == b.solid)
+            },
+            (LegendEntry::Entity(a), LegendEntry::Entity(b)) => {
+                (a.texture == b.texture) && (a.archetype == b.archetype) && (a.solid == b.solid)
+            },
+            (LegendEntry::Spawn(a), LegendEntry::Spawn(b)) => a.facing == b.facing,
+            _ => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::Legend;
+
+    const WALL: &'static str = "
+[[tile]]
+color = [0, 0, 0]
+symbol = \"#\"
+name = \"wall\"
+texture = \"wall\"
+";
+
+    fn validate(extra: &str) -> Result<(), String> {
+        Legend::parse(&format!("{}{}", WALL, extra)).unwrap().validate()
+    }
+
+    #[test]
+    fn level_legends_are_valid() {
+        for file_path in ["res/maps/level1.toml", "res/maps/level2.toml"].iter() {
+            Legend::load(Path::new(file_path)).unwrap();
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_colors() {
+        let result = validate("
+[[entity]]
+color = [0, 0, 0]
+symbol = \"T\"
+archetype = \"treasure\"
+texture = \"treasure\"
+");
+        assert!(result.unwrap_err().contains("color"));
+    }
+
+    #[test]
+    fn rejects_duplicate_symbols() {
+        let result = validate("
+[[spawn]]
+color = [255, 255, 0]
+symbol = \"#\"
+facing = \"east\"
+");
+        assert!(result.unwrap_err().contains("symbol"));
+    }
+
+    #[test]
+    fn rejects_empty_floor_symbol() {
+        let result = validate("
+[[tile]]
+color = [1, 1, 1]
+symbol = \".\"
+name = \"stone\"
+texture = \"stone\"
+");
+        assert!(result.unwrap_err().contains("empty floor"));
+    }
+
+    #[test]
+    fn rejects_entries_that_place_the_same_thing() {
+        let result = validate("
+[[tile]]
+color = [1, 1, 1]
+symbol = \"W\"
+name = \"another wall\"
+texture = \"wall\"
+");
+        assert!(result.unwrap_err().contains("told apart"));
+    }
+
+    #[test]
+    fn tiles_with_different_faces_are_different() {
+        let result = validate("
+[[tile]]
+color = [1, 1, 1]
+symbol = \"D\"
+name = \"decorated wall\"
+texture = \"wall\"
+
+[tile.faces]
+south = \"skull_wall\"
+");
+        assert!(result.is_ok());
+    }
+}
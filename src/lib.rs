@@ -0,0 +1,72 @@
+extern crate sdl2;
+extern crate time;
+extern crate pathfinding;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+
+pub mod error;
+pub mod texture;
+pub mod assets;
+pub mod map;
+pub mod legend;
+pub mod text_map;
+pub mod campaign;
+pub mod random;
+pub mod generator;
+pub mod entity;
+pub mod simulation;
+pub mod replay;
+pub mod save;
+pub mod raycast;
+pub mod render;
+pub mod audio;
+pub mod input;
+pub mod controller;
+pub mod scores;
+pub mod config;
+pub mod watch;
+pub mod game;
+
+use sdl2::pixels::Color;
+
+pub const WINDOW_TITLE: &'static str = "SPIM Quest";
+pub const WINDOW_WIDTH: u32 = 640;
+pub const WINDOW_HEIGHT: u32 = 480;
+pub const IS_FULLSCREEN: bool = true;
+pub const FIELD_OF_VIEW: f64 = 90.0;
+pub const CAMPAIGN_PATH: &'static str = "res/campaign.toml";
+pub const ASSETS_PATH: &'static str = "res/assets.toml";
+pub const MODS_DIR: &'static str = "mods";
+pub const CONFIG_PATH: &'static str = "spimquest.toml";
+pub const CONTROLS_PATH: &'static str = "controls.toml";
+pub const SCORES_PATH: &'static str = "scores.toml";
+pub const SAVES_DIR: &'static str = "saves";
+
+pub const COLOR_BLACK: Color = Color {r: 0, g: 0, b: 0, a: 255};
+pub const COLOR_WHITE: Color = Color {r: 255, g: 255, b: 255, a: 255};
+pub const COLOR_RED: Color = Color {r: 255, g: 0, b: 0, a: 255};
+pub const COLOR_GREEN: Color = Color {r: 0, g: 255, b: 0, a: 255};
+pub const COLOR_BLUE: Color = Color {r: 0, g: 0, b: 255, a: 255};
+pub const COLOR_MAGENTA: Color = Color {r: 255, g: 0, b: 255, a: 255};
+
+// The textures the game draws itself, by their names in the asset manifest.
+// Everything else is named by map legends.
+pub const TEXTURE_WALL: &'static str = "wall";
+pub const TEXTURE_CEILING: &'static str = "ceiling";
+pub const TEXTURE_FLOOR: &'static str = "floor";
+pub const TEXTURE_EXIT: &'static str = "exit";
+
+pub const TWO_PI: f64 = 2.0 * std::f64::consts::PI;
+
+pub fn wrap_angle(angle: f64) -> f64 {
+    if angle < 0.0 {
+        return angle + TWO_PI;
+    }
+    else if angle >= TWO_PI {
+        return angle - TWO_PI;
+    }
+
+    angle
+}
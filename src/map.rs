@@ -0,0 +1,588 @@
+use std::f64::consts::PI;
+use std::path::Path;
+use sdl2::pixels::Color;
+use texture::Texture;
+use error;
+use error::Error;
+use entity::{Entity, Archetype};
+use legend::{Legend, LegendEntry};
+use raycast::TileFace;
+use {TEXTURE_WALL, TEXTURE_CEILING, TEXTURE_FLOOR, TEXTURE_EXIT};
+
+// A compass direction on the map. North is towards the top of the map (lowest y).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Facing {
+    North,
+    East,
+    South,
+    West
+}
+
+impl Facing {
+    // The player rotation that looks in this direction
+    pub fn to_rotation(&self) -> f64 {
+        match *self {
+            Facing::East => 0.0,
+            Facing::South => PI / 2.0,
+            Facing::West => PI,
+            Facing::North => PI * 1.5
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Facing> {
+        match name {
+            "north" => Some(Facing::North),
+            "east" => Some(Facing::East),
+            "south" => Some(Facing::South),
+            "west" => Some(Facing::West),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Facing::North => "north",
+            Facing::East => "east",
+            Facing::South => "south",
+            Facing::West => "west"
+        }
+    }
+}
+
+// The tile the player starts on, and which way they face
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Spawn {
+    pub x: u32,
+    pub y: u32,
+    pub facing: Facing
+}
+
+impl Spawn {
+    pub fn new(x: u32, y: u32, facing: Facing) -> Spawn {
+        Spawn {
+            x: x,
+            y: y,
+            facing: facing
+        }
+    }
+}
+
+// Extra information about a map that isn't part of the tile grid
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MapInfo {
+    #[serde(default)]
+    pub name: String,
+    // The name of a track in the asset manifest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub music: Option<String>,
+    // Maps can place the spawn here instead of marking it on the grid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<Spawn>
+}
+
+pub struct Map {
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<Option<Tile>>,
+    pub entities: Vec<Entity>,
+    pub spawn: Spawn,
+    pub info: MapInfo,
+    pub legend: Legend,
+    // The name of every texture used on the map. Tiles and entities refer to textures by their index in here.
+    pub texture_names: Vec<String>
+}
+
+impl Map {
+    pub fn new(width: u32, height: u32, tiles: Vec<Option<Tile>>, entities: Vec<Entity>) -> Map {
+        Map {
+            width: width,
+            height: height,
+            tiles: tiles,
+            entities: entities,
+            spawn: Spawn::new(0, 0, Facing::East),
+            info: MapInfo::default(),
+            legend: Legend::default(),
+            texture_names: builtin_texture_names()
+        }
+    }
+
+    // The id of the texture with the given name, which is added to the map if it isn't used yet
+    pub fn texture_id(&mut self, name: &str) -> u32 {
+        add_texture_name(&mut self.texture_names, name)
+    }
+
+    pub fn find_texture_id(&self, name: &str) -> Option<u32> {
+        self.texture_names.iter().position(|texture_name| texture_name == name).map(|id| id as u32)
+    }
+
+    pub fn texture_name(&self, id: u32) -> Option<&str> {
+        self.texture_names.get(id as usize).map(|name| name.as_str())
+    }
+
+    pub fn get_tile(&self, x: i32, y: i32) -> Option<Tile> {
+        if !self.is_inside(x, y) {
+            None
+        }
+        else
+        {
+            self.tiles[((y * self.width as i32) + x) as usize]
+        }
+    }
+
+    // Whether the player and entities are blocked from entering the given tile
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        if let Some(tile) = self.get_tile(x, y) {
+            if tile.solid {
+                return true;
+            }
+        }
+
+        self.entities.iter().any(|ent| ent.solid && (ent.x as i32 == x) && (ent.y as i32 == y))
+    }
+
+    // Loads a map in whichever format the file extension says (.png images or .txt text maps)
+    pub fn load(file_path: &str) -> error::Result<Map> {
+        if is_text_map(file_path) {
+            Map::load_text(file_path)
+        }
+        else {
+            Map::load_image(file_path)
+        }
+    }
+
+    // Saves a map in whichever format the file extension says, along with its legend
+    pub fn save(&self, file_path: &str) -> error::Result<()> {
+        if is_text_map(file_path) {
+            self.save_text(file_path)
+        }
+        else {
+            self.save_image(file_path)
+        }
+    }
+
+    // Loads a map image, along with the legend next to it that says what each pixel color means
+    pub fn load_image(file_path: &str) -> error::Result<Map> {
+        let texture: Texture = Texture::load(file_path)?;
+
+        let legend: Legend = Legend::load(&Legend::path_for_map(Path::new(file_path)))?;
+        let info: MapInfo = legend.map.clone();
+
+        Map::build(texture.width, texture.height, legend, info, |legend, x, y| {
+            Ok(legend.find(texture.get_pixel(x, y)))
+        })
+    }
+
+    // Saves the map as an image, along with its legend
+    pub fn save_image(&self, file_path: &str) -> error::Result<()> {
+        let mut pixels: Vec<Color> = Vec::new();
+        pixels.resize((self.width * self.height) as usize, COLOR_EMPTY);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some(entry) = self.get_legend_entry(x, y)? {
+                    pixels[((y * self.width) + x) as usize] = entry.color();
+                }
+            }
+        }
+
+        Texture::new(self.width, self.height, pixels).save(file_path)?;
+
+        let mut legend: Legend = self.legend.clone();
+        legend.map = self.info.clone();
+        legend.save(&Legend::path_for_map(Path::new(file_path)))?;
+        Ok(())
+    }
+
+    // Builds a map out of the legend entry for every tile (None for empty floor).
+    // Both map formats go through here, so they always produce the same map for the same entries.
+    // Fails unless the map has exactly one spawn (on the grid or in the info), and it isn't on a solid tile.
+    pub fn build<F>(width: u32, height: u32, legend: Legend, info: MapInfo, get_entry: F) -> error::Result<Map>
+        where F: Fn(&Legend, u32, u32) -> error::Result<Option<LegendEntry>> {
+        let mut spawns: Vec<Spawn> = Vec::new();
+        if let Some(spawn) = info.spawn {
+            spawns.push(spawn);
+        }
+
+        let mut texture_names: Vec<String> = builtin_texture_names();
+        let mut next_ent_id: u32 = 0;
+        let mut entities: Vec<Entity> = Vec::new();
+        let mut tiles: Vec<Option<Tile>> = Vec::new();
+        tiles.resize((width * height) as usize, None);
+
+        for x in 0..width {
+            for y in 0..height {
+                let index: usize = ((y * width) + x) as usize;
+
+                match get_entry(&legend, x, y)? {
+                    Some(LegendEntry::Tile(tile)) => {
+                        let texture_id: u32 = add_texture_name(&mut texture_names, &tile.texture);
+                        let mut faces: [u32; 4] = [texture_id; 4];
+                        for (face, name) in tile.faces.textures(&tile.texture).iter().enumerate() {
+                            faces[face] = add_texture_name(&mut texture_names, name);
+                        }
+
+                        tiles[index] = Some(Tile::new(x, y, texture_id, faces, tile.solid));
+                    },
+                    Some(LegendEntry::Entity(entity)) => {
+                        let texture_id: u32 = add_texture_name(&mut texture_names, &entity.texture);
+                        entities.push(Entity::new(next_ent_id, x as f64, y as f64, texture_id, entity.archetype, entity.solid));
+                        next_ent_id += 1;
+                    },
+                    Some(LegendEntry::Spawn(spawn)) => {
+                        spawns.push(Spawn::new(x, y, spawn.facing));
+                    },
+
+                    None => {}
+                }
+            }
+        }
+
+        let spawn: Spawn = match spawns.len() {
+            0 => return Err(invalid_map(&info, "there is no player spawn".to_string())),
+            1 => spawns[0],
+            _ => {
+                let positions: Vec<String> = spawns.iter().map(|spawn| format!("{}, {}", spawn.x, spawn.y)).collect();
+                return Err(invalid_map(&info, format!("there are {} player spawns ({})", spawns.len(), positions.join("; "))));
+            }
+        };
+
+        let mut map: Map = Map::new(width, height, tiles, entities);
+        map.spawn = spawn;
+        map.info = info;
+        map.legend = legend;
+        map.texture_names = texture_names;
+
+        if (spawn.x >= width) || (spawn.y >= height) {
+            return Err(invalid_map(&map.info, format!("the player spawn at {}, {} is outside of the map", spawn.x, spawn.y)));
+        }
+
+        if map.is_solid(spawn.x as i32, spawn.y as i32) {
+            return Err(invalid_map(&map.info, format!("the player spawn at {}, {} is on a solid tile", spawn.x, spawn.y)));
+        }
+
+        Ok(map)
+    }
+
+    // Finds the legend entry describing whatever is at the given tile (None for empty floor)
+    pub fn get_legend_entry<'a>(&'a self, x: u32, y: u32) -> error::Result<Option<LegendEntry<'a>>> {
+        if let Some(tile) = self.get_tile(x as i32, y as i32) {
+            for entry in self.legend.tile.iter() {
+                let faces_match: bool = entry.faces.textures(&entry.texture).iter()
+                    .zip(tile.faces.iter())
+                    .all(|(name, &id)| self.texture_name(id) == Some(*name));
+
+                if (self.texture_name(tile.id) == Some(entry.texture.as_str())) && faces_match && (entry.solid == tile.solid) {
+                    return Ok(Some(LegendEntry::Tile(entry)));
+                }
+            }
+
+            return Err(Error::InvalidMap(format!("No legend entry for the tile at {}, {}", x, y)));
+        }
+
+        if let Some(ent) = self.entities.iter().find(|ent| (ent.x as u32 == x) && (ent.y as u32 == y)) {
+            for entry in self.legend.entity.iter() {
+                if (self.texture_name(ent.texture_id) == Some(entry.texture.as_str())) && (entry.archetype == ent.archetype) && (entry.solid == ent.solid) {
+                    return Ok(Some(LegendEntry::Entity(entry)));
+                }
+            }
+
+            return Err(Error::InvalidMap(format!("No legend entry for the entity at {}, {}", x, y)));
+        }
+
+        // The spawn only goes on the grid if the info doesn't already hold it
+        if self.info.spawn.is_none() && (self.spawn.x == x) && (self.spawn.y == y) {
+            if let Some(entry) = self.legend.spawn.iter().find(|entry| entry.facing == self.spawn.facing) {
+                return Ok(Some(LegendEntry::Spawn(entry)));
+            }
+
+            return Err(Error::InvalidMap(format!("No legend entry for a spawn facing {}", self.spawn.facing.name())));
+        }
+
+        Ok(None)
+    }
+
+    // Whether the given tile is on the map at all
+    pub fn is_inside(&self, x: i32, y: i32) -> bool {
+        (x >= 0) && (y >= 0) && (x < self.width as i32) && (y < self.height as i32)
+    }
+
+    // The open tiles next to the given one, for pathfinding.
+    // Tiles off the edge of the map are left out, since maps don't need walls all the way around them.
+    pub fn get_neighbors(&self, pos: Position) -> Vec<(Position, usize)> {
+        let mut neighbors: Vec<(Position, usize)> = Vec::new();
+
+        let up_pos = Position::new(pos.x, pos.y - 1);
+        let down_pos = Position::new(pos.x, pos.y + 1);
+        let left_pos = Position::new(pos.x - 1, pos.y);
+        let right_pos = Position::new(pos.x + 1, pos.y);
+
+        for neighbor in [up_pos, down_pos, left_pos, right_pos].iter() {
+            if self.is_inside(neighbor.x, neighbor.y) && !self.is_solid(neighbor.x, neighbor.y) {
+                neighbors.push((*neighbor, 1));
+            }
+        }
+
+        neighbors
+    }
+
+    pub fn get_overlap_ent(&self, x: f64, y: f64) -> Option<u32> {
+        // TODO:
+        // Make this check collisions in the center of the tile (0.5 offset)
+
+        for ent in self.entities.iter() {
+            // Props are only there to look at
+            if ent.archetype == Archetype::Prop {
+                continue;
+            }
+
+            if (x - (ent.x + 0.5)).abs() <= 0.45 &&
+               (y - (ent.y + 0.5)).abs() <= 0.45 {
+                return Some(ent.id);
+            }
+        }
+
+        return None;
+    }
+
+    // Adds a new entity to the map, returning its id
+    pub fn add_ent(&mut self, x: f64, y: f64, texture_id: u32, archetype: Archetype, solid: bool) -> u32 {
+        let id: u32 = self.entities.iter().map(|ent| ent.id + 1).max().unwrap_or(0);
+        self.entities.push(Entity::new(id, x, y, texture_id, archetype, solid));
+
+        id
+    }
+
+    pub fn delete_ent(&mut self, ent_id: u32) {
+        self.entities.retain(|ent| ent.id != ent_id);
+    }
+
+    pub fn is_treasure(&self, ent_id: u32) -> bool {
+        if let Some(ent) = self.entities.iter().find(|ent| ent.id == ent_id) {
+            return ent.archetype == Archetype::Treasure;
+        }
+
+        return false;
+    }
+
+    pub fn is_exit(&self, ent_id: u32) -> bool {
+        if let Some(ent) = self.entities.iter().find(|ent| ent.id == ent_id) {
+            return ent.archetype == Archetype::Exit;
+        }
+
+        return false;
+    }
+
+    pub fn count_treasure(&self) -> u32 {
+        self.entities.iter().filter(|ent| ent.archetype == Archetype::Treasure).count() as u32
+    }
+
+    pub fn is_deadly(&self, ent_id: u32) -> bool {
+        if let Some(ent) = self.entities.iter().find(|ent| ent.id == ent_id) {
+            return ent.archetype.is_deadly();
+        }
+
+        return false;
+    }
+}
+
+// Empty floor in map images
+const COLOR_EMPTY: Color = Color {r: 0, g: 0, b: 0, a: 0};
+
+fn invalid_map(info: &MapInfo, message: String) -> Error {
+    Error::InvalidMap(format!("Invalid map '{}': {}", info.name, message))
+}
+
+fn is_text_map(file_path: &str) -> bool {
+    Path::new(file_path).extension().map_or(false, |extension| extension == "txt")
+}
+
+// The textures every map needs, whether or not its legend uses them
+fn builtin_texture_names() -> Vec<String> {
+    vec![TEXTURE_WALL.to_string(), TEXTURE_CEILING.to_string(), TEXTURE_FLOOR.to_string(), TEXTURE_EXIT.to_string()]
+}
+
+fn add_texture_name(texture_names: &mut Vec<String>, name: &str) -> u32 {
+    match texture_names.iter().position(|texture_name| texture_name == name) {
+        Some(id) => id as u32,
+        None => {
+            texture_names.push(name.to_string());
+            (texture_names.len() - 1) as u32
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    // The tile's texture id, and the texture id of each of its faces (north, east, south then west)
+    pub id: u32,
+    pub faces: [u32; 4],
+    pub solid: bool
+}
+
+impl Tile {
+    pub fn new(x: u32, y: u32, id: u32, faces: [u32; 4], solid: bool) -> Tile {
+        Tile {
+            x: x,
+            y: y,
+            id: id,
+            faces: faces,
+            solid: solid
+        }
+    }
+
+    // The texture id drawn on the given face
+    pub fn face_texture_id(&self, face: TileFace) -> u32 {
+        match face {
+            TileFace::North => self.faces[0],
+            TileFace::East => self.faces[1],
+            TileFace::South => self.faces[2],
+            TileFace::West => self.faces[3]
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialOrd, PartialEq)]
+pub struct Vector2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vector2 {
+    pub fn new(x: f32, y: f32) -> Vector2 {
+        Vector2 { x: x, y: y }
+    }
+
+    pub fn distance(first: &Vector2, other: &Vector2) -> f32 {
+        let (x1, x2) = if first.x > other.x { (first.x, other.x) } else { (other.x, first.x) };
+        let (y1, y2) = if first.y > other.y { (first.y, other.y) } else { (other.y, first.y) };
+
+         ((x1 - x2) + (y1 - y2)) as f32
+     }
+}
+
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    pub fn new(x: i32, y: i32) -> Position {
+        Position { x: x, y: y }
+    }
+
+    pub fn to_index(&self, width: usize) -> usize {
+        ((self.y * width as i32) + self.x) as usize
+    }
+
+    pub fn distance(first: &Position, other: &Position) -> usize {
+        let (x1, x2) = if first.x > other.x { (first.x, other.x) } else { (other.x, first.x) };
+        let (y1, y2) = if first.y > other.y { (first.y, other.y) } else { (other.y, first.y) };
+
+         ((x1 - x2) + (y1 - y2)) as usize
+     }
+}
+
+#[cfg(test)]
+mod tests {
+    use entity::{Archetype, Entity};
+    use legend::Legend;
+    use super::{Facing, Map, MapInfo, Position, Spawn, Tile};
+
+    const LEGEND: &'static str = "
+[[tile]]
+color = [0, 0, 0]
+symbol = \"#\"
+name = \"wall\"
+texture = \"wall\"
+solid = true
+
+[[entity]]
+color = [0, 128, 0]
+symbol = \"B\"
+archetype = \"prop\"
+texture = \"barrel\"
+solid = true
+
+[[spawn]]
+color = [255, 255, 0]
+symbol = \"@\"
+facing = \"east\"
+";
+
+    // Builds a map from rows of legend symbols, the way text maps are, and returns why it failed
+    fn build_error(rows: &[&str], spawn: Option<Spawn>) -> String {
+        let legend: Legend = Legend::parse(LEGEND).unwrap();
+        let info = MapInfo {
+            spawn: spawn,
+            ..MapInfo::default()
+        };
+
+        let result = Map::build(rows[0].len() as u32, rows.len() as u32, legend, info, |legend, x, y| {
+            Ok(legend.find_symbol(rows[y as usize].as_bytes()[x as usize] as char))
+        });
+
+        match result {
+            Ok(_) => String::new(),
+            Err(e) => e.to_string()
+        }
+    }
+
+    // A map with no walls around its edges, and walls at the given tiles
+    fn open_map(width: u32, height: u32, walls: &[(u32, u32)]) -> Map {
+        let mut tiles: Vec<Option<Tile>> = vec![None; (width * height) as usize];
+        for &(x, y) in walls.iter() {
+            tiles[((y * width) + x) as usize] = Some(Tile::new(x, y, 0, [0; 4], true));
+        }
+
+        Map::new(width, height, tiles, Vec::new())
+    }
+
+    #[test]
+    fn neighbors_stay_on_the_map() {
+        let map: Map = open_map(3, 3, &[]);
+
+        assert_eq!(map.get_neighbors(Position::new(0, 0)).len(), 2);
+        assert_eq!(map.get_neighbors(Position::new(1, 0)).len(), 3);
+        assert_eq!(map.get_neighbors(Position::new(1, 1)).len(), 4);
+    }
+
+    #[test]
+    fn pathfinding_gives_up_on_unreachable_goals() {
+        let mut map: Map = open_map(5, 5, &[(3, 4), (4, 3), (3, 3)]);
+        map.entities.push(Entity::new(0, 0.5, 0.5, 0, Archetype::Monster, false));
+
+        map.pathfind(Position::new(4, 4));
+        assert_eq!(map.entities[0].destination, Position::new(0, 0));
+    }
+
+    #[test]
+    fn needs_a_spawn() {
+        assert!(build_error(&["###", "#.#", "###"], None).contains("there is no player spawn"));
+    }
+
+    #[test]
+    fn needs_only_one_spawn() {
+        assert!(build_error(&["####", "#@@#", "####"], None).contains("there are 2 player spawns"));
+        assert!(build_error(&["###", "#@#", "#.#", "###"], Some(Spawn::new(1, 2, Facing::North))).contains("there are 2 player spawns"));
+    }
+
+    #[test]
+    fn spawn_has_to_be_open() {
+        assert!(build_error(&["###", "#.#", "###"], Some(Spawn::new(0, 1, Facing::East))).contains("on a solid tile"));
+        assert!(build_error(&["###", "#B#", "###"], Some(Spawn::new(1, 1, Facing::East))).contains("on a solid tile"));
+    }
+
+    #[test]
+    fn spawn_has_to_be_on_the_map() {
+        assert!(build_error(&["###", "#.#", "###"], Some(Spawn::new(5, 1, Facing::East))).contains("outside of the map"));
+    }
+
+    #[test]
+    fn one_open_spawn_is_fine() {
+        assert_eq!(build_error(&["###", "#@#", "###"], None), "");
+    }
+}
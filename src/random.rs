@@ -0,0 +1,50 @@
+// A small seeded random number generator (SplitMix64).
+// It's written out here instead of pulled from a crate so that the same seed gives the same numbers
+// on every platform and every version, which is what makes seeds shareable.
+#[derive(Copy, Clone, Debug)]
+pub struct Random {
+    state: u64
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            state: seed
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z: u64 = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // A number in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // A number in [min, max). Returns min if the range is empty.
+    pub fn range(&mut self, min: u32, max: u32) -> u32 {
+        if max <= min {
+            return min;
+        }
+
+        min + (self.next_u64() % (max - min) as u64) as u32
+    }
+
+    // True with the given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j: usize = self.range(0, (i + 1) as u32) as usize;
+            items.swap(i, j);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::f64;
+use map::Map;
+
+// Rays that travel further than this without hitting anything are considered misses
+pub const MAX_RAY_DISTANCE: f64 = 64.0;
+
+// The face of a tile that a ray hit.
+// North is the face at the top (lowest y) of the tile, West is the face at the left (lowest x).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TileFace {
+    North,
+    East,
+    South,
+    West
+}
+
+impl TileFace {
+    // Whether this face runs along the x axis (hit while stepping vertically through the grid)
+    pub fn is_horizontal(&self) -> bool {
+        match *self {
+            TileFace::North | TileFace::South => true,
+            TileFace::East | TileFace::West => false
+        }
+    }
+}
+
+pub struct RaycastHit {
+    pub x: f64,
+    pub y: f64,
+    pub tile_x: u32,
+    pub tile_y: u32,
+    pub face: TileFace,
+    // The texture of the face that was hit
+    pub texture_id: u32,
+    pub distance: f64
+}
+
+impl Map {
+    // Walks the grid one tile boundary at a time (DDA) until a solid tile is hit.
+    // Returns None if nothing is hit within max_distance, so this always terminates,
+    // even on maps without perimeter walls.
+    pub fn raycast(&self, origin_x: f64, origin_y: f64, angle: f64, max_distance: f64) -> Option<RaycastHit> {
+        let direction_x: f64 = angle.cos();
+        let direction_y: f64 = angle.sin();
+
+        let mut tile_x: i32 = origin_x.floor() as i32;
+        let mut tile_y: i32 = origin_y.floor() as i32;
+
+        // The distance along the ray between two x (or y) tile boundaries
+        let delta_distance_x: f64 = if direction_x == 0.0 { f64::INFINITY } else { (1.0 / direction_x).abs() };
+        let delta_distance_y: f64 = if direction_y == 0.0 { f64::INFINITY } else { (1.0 / direction_y).abs() };
+
+        // The distance along the ray to the first x (or y) tile boundary
+        let (step_x, mut side_distance_x): (i32, f64) = if direction_x == 0.0 {
+            (0, f64::INFINITY)
+        } else if direction_x < 0.0 {
+            (-1, (origin_x - tile_x as f64) * delta_distance_x)
+        } else {
+            (1, (tile_x as f64 + 1.0 - origin_x) * delta_distance_x)
+        };
+
+        let (step_y, mut side_distance_y): (i32, f64) = if direction_y == 0.0 {
+            (0, f64::INFINITY)
+        } else if direction_y < 0.0 {
+            (-1, (origin_y - tile_y as f64) * delta_distance_y)
+        } else {
+            (1, (tile_y as f64 + 1.0 - origin_y) * delta_distance_y)
+        };
+
+        loop {
+            let distance: f64;
+            let face: TileFace;
+
+            // Step into whichever neighboring tile the ray reaches first
+            if side_distance_x < side_distance_y {
+                distance = side_distance_x;
+                side_distance_x += delta_distance_x;
+                tile_x += step_x;
+                face = if step_x > 0 { TileFace::West } else { TileFace::East };
+            }
+            else {
+                distance = side_distance_y;
+                side_distance_y += delta_distance_y;
+                tile_y += step_y;
+                face = if step_y > 0 { TileFace::North } else { TileFace::South };
+            }
+
+            if distance > max_distance {
+                return None;
+            }
+
+            if let Some(tile) = self.get_tile(tile_x, tile_y) {
+                return Some(RaycastHit {
+                    x: origin_x + (direction_x * distance),
+                    y: origin_y + (direction_y * distance),
+                    tile_x: tile.x,
+                    tile_y: tile.y,
+                    face: face,
+                    texture_id: tile.face_texture_id(face),
+                    distance: distance
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+    use map::{Map, Tile};
+    use super::{RaycastHit, TileFace, MAX_RAY_DISTANCE};
+
+    // A size by size map, walled all the way around if is_walled. Each face of the walls has its own
+    // texture id (1 to 4 for north, east, south and west), so the face that was hit shows in the texture too.
+    fn square_map(size: u32, is_walled: bool) -> Map {
+        let mut tiles: Vec<Option<Tile>> = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let is_edge: bool = (x == 0) || (y == 0) || (x == size - 1) || (y == size - 1);
+                tiles.push(if is_walled && is_edge { Some(Tile::new(x, y, 1, [1, 2, 3, 4], true)) } else { None });
+            }
+        }
+
+        Map::new(size, size, tiles, Vec::new())
+    }
+
+    fn assert_hit(hit: Option<RaycastHit>, tile: (u32, u32), face: TileFace, texture_id: u32, distance: f64) {
+        let hit: RaycastHit = hit.expect("the ray should have hit a wall");
+        assert_eq!((hit.tile_x, hit.tile_y), tile);
+        assert_eq!(hit.face, face);
+        assert_eq!(hit.texture_id, texture_id);
+        assert!((hit.distance - distance).abs() < 1e-9, "hit at {} instead of {}", hit.distance, distance);
+    }
+
+    #[test]
+    fn hits_the_nearest_face() {
+        let map: Map = square_map(5, true);
+
+        assert_hit(map.raycast(2.5, 2.5, 0.0, MAX_RAY_DISTANCE), (4, 2), TileFace::West, 4, 1.5);
+        assert_hit(map.raycast(2.5, 2.5, PI, MAX_RAY_DISTANCE), (0, 2), TileFace::East, 2, 1.5);
+        assert_hit(map.raycast(2.5, 2.5, PI / 2.0, MAX_RAY_DISTANCE), (2, 4), TileFace::North, 1, 1.5);
+        assert_hit(map.raycast(2.5, 2.5, -PI / 2.0, MAX_RAY_DISTANCE), (2, 0), TileFace::South, 3, 1.5);
+
+        // Two tiles across for every one down, so the east wall is reached three quarters of a tile further down
+        let angle: f64 = f64::atan2(1.0, 2.0);
+        assert_hit(map.raycast(2.5, 2.5, angle, MAX_RAY_DISTANCE), (4, 3), TileFace::West, 4, f64::hypot(1.5, 0.75));
+    }
+
+    #[test]
+    fn misses_past_the_max_distance() {
+        let map: Map = square_map(5, true);
+
+        assert!(map.raycast(2.5, 2.5, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn terminates_on_maps_without_perimeter_walls() {
+        let map: Map = square_map(5, false);
+
+        for step in 0..16 {
+            let angle: f64 = step as f64 * (PI / 8.0);
+            assert!(map.raycast(2.5, 2.5, angle, MAX_RAY_DISTANCE).is_none());
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::thread;
+use sdl2::pixels::Color;
+use map::Map;
+use entity::Entity;
+use texture::Texture;
+use raycast::{RaycastHit, MAX_RAY_DISTANCE};
+use {wrap_angle, TEXTURE_WALL, TEXTURE_CEILING, TEXTURE_FLOOR};
+
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+
+    // The distance to the wall in each column
+    pub depth_buffer: Vec<f64>
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        let mut pixels: Vec<u8> = Vec::new();
+        pixels.resize((width * height * 4) as usize, 0);
+
+        let mut depth_buffer: Vec<f64> = Vec::new();
+        depth_buffer.resize(width as usize, 0.0);
+
+        Framebuffer {
+            width: width,
+            height: height,
+            pixels: pixels,
+            depth_buffer: depth_buffer
+        }
+    }
+
+    // The number of bytes in a single row of pixels
+    pub fn pitch(&self) -> usize {
+        (self.width * 4) as usize
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel[0] = color.r;
+            pixel[1] = color.g;
+            pixel[2] = color.b;
+            pixel[3] = color.a;
+        }
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        let index: usize = (((y * self.width) + x) * 4) as usize;
+
+        Color {
+            r: self.pixels[index],
+            g: self.pixels[index + 1],
+            b: self.pixels[index + 2],
+            a: self.pixels[index + 3]
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        let index: usize = (((y * self.width) + x) * 4) as usize;
+
+        self.pixels[index] = color.r;
+        self.pixels[index + 1] = color.g;
+        self.pixels[index + 2] = color.b;
+        self.pixels[index + 3] = color.a;
+    }
+}
+
+// Everything needed to project the world onto the screen
+#[derive(Copy, Clone)]
+struct Camera {
+    origin_x: f64,
+    origin_y: f64,
+    rotation: f64,
+    projection_width: u32,
+    projection_height: u32,
+    projection_distance: f64
+}
+
+// Where the world is seen from. Entities are drawn interpolation (from 0 to 1) of the way from
+// where they were before the last tick to where they are now.
+#[derive(Copy, Clone)]
+pub struct View {
+    pub origin_x: f64,
+    pub origin_y: f64,
+    pub rotation: f64,
+    // The horizontal field of view, in degrees
+    pub field_of_view: f64,
+    pub interpolation: f64
+}
+
+// The number of threads to render with when none is configured
+pub fn default_thread_count() -> usize {
+    thread::available_parallelism().map(|count| count.get()).unwrap_or(1)
+}
+
+const LIGHT_RADIUS: f64 = 5.0;
+const TILE_WIDTH: f64 = 1.0;
+const TILE_HEIGHT: f64 = 1.0;
+const PLAYER_HEIGHT: f64 = 0.5;
+
+fn calculate_lighting(distance: f64, light_radius: f64) -> f64 {
+    ((light_radius - distance) * (1.0 / light_radius)).clamp(0.0, 1.0)
+}
+
+// Textures that aren't loaded, or have no pixels to sample, are left out of the picture rather than bringing the game down
+fn get_texture(textures: &HashMap<u32, Texture>, id: u32) -> Option<&Texture> {
+    textures.get(&id).filter(|texture| (texture.width > 0) && (texture.height > 0))
+}
+
+fn get_named_texture<'a>(textures: &'a HashMap<u32, Texture>, map: &Map, name: &str) -> Option<&'a Texture> {
+    map.find_texture_id(name).and_then(|id| get_texture(textures, id))
+}
+
+// Renders the world as seen from the view into the framebuffer.
+// This doesn't touch SDL video at all, so it can run without a window.
+// The work is split across thread_count threads, each writing straight into its own part of the framebuffer:
+// first the rays are cast in bands of columns, then the pixels are drawn in bands of rows.
+// Every pixel is worked out the same way no matter which thread it lands on, so the output doesn't depend on the thread count.
+pub fn render_world(framebuffer: &mut Framebuffer, map: &Map, textures: &HashMap<u32, Texture>, view: &View, thread_count: usize) {
+    let origin_x: f64 = view.origin_x;
+    let origin_y: f64 = view.origin_y;
+    let rotation: f64 = view.rotation;
+    let field_of_view: f64 = view.field_of_view;
+    let interpolation: f64 = view.interpolation;
+
+    let projection_width: u32 = framebuffer.width;
+    let projection_height: u32 = framebuffer.height;
+    let projection_distance: f64 = (projection_width as f64 / 2.0) / f64::tan(field_of_view.to_radians() / 2.0);
+
+    if (projection_width == 0) || (projection_height == 0) {
+        return;
+    }
+
+    let camera = Camera {
+        origin_x: origin_x,
+        origin_y: origin_y,
+        rotation: rotation,
+        projection_width: projection_width,
+        projection_height: projection_height,
+        projection_distance: projection_distance
+    };
+
+    let surfaces = match (get_named_texture(textures, map, TEXTURE_WALL), get_named_texture(textures, map, TEXTURE_CEILING), get_named_texture(textures, map, TEXTURE_FLOOR)) {
+        (Some(wall), Some(ceiling), Some(floor)) => Surfaces { wall: wall, ceiling: ceiling, floor: floor },
+        _ => return
+    };
+
+    let thread_count: usize = thread_count.max(1);
+
+    // Rays, in bands of columns
+    let band_width: usize = (projection_width as usize).div_ceil(thread_count);
+    let columns: Vec<Column> = if thread_count == 1 {
+        cast_columns(&mut framebuffer.depth_buffer, 0, &camera, map, textures, &surfaces)
+    }
+    else {
+        thread::scope(|scope| {
+            let bands: Vec<_> = framebuffer.depth_buffer.chunks_mut(band_width).enumerate()
+                .map(|(index, depth_buffer)| {
+                    let (camera, surfaces) = (&camera, &surfaces);
+                    scope.spawn(move || cast_columns(depth_buffer, (index * band_width) as u32, camera, map, textures, surfaces))
+                })
+                .collect();
+
+            bands.into_iter().flat_map(|band| band.join().unwrap()).collect()
+        })
+    };
+
+    // Pixels, in bands of rows
+    let pitch: usize = framebuffer.pitch();
+    let band_height: usize = (projection_height as usize).div_ceil(thread_count);
+    if thread_count == 1 {
+        draw_rows(&mut framebuffer.pixels, 0, &camera, &columns, &surfaces);
+    }
+    else {
+        thread::scope(|scope| {
+            for (index, pixels) in framebuffer.pixels.chunks_mut(band_height * pitch).enumerate() {
+                let (camera, columns, surfaces) = (&camera, &columns, &surfaces);
+                scope.spawn(move || draw_rows(pixels, (index * band_height) as u32, camera, columns, surfaces));
+            }
+        });
+    }
+
+    // Sort sprites (far to near)
+    let mut sprites: Vec<Entity> = map.entities.clone();
+    for sprite in sprites.iter_mut() {
+        sprite.x = sprite.previous_x + ((sprite.x - sprite.previous_x) * interpolation);
+        sprite.y = sprite.previous_y + ((sprite.y - sprite.previous_y) * interpolation);
+    }
+
+    sprites.sort_by(|a, b| {
+        let a_distance: f64 = (a.x - origin_x).powi(2) + (a.y - origin_y).powi(2);
+        let b_distance: f64 = (b.x - origin_x).powi(2) + (b.y - origin_y).powi(2);
+
+        b_distance.partial_cmp(&a_distance).unwrap()
+    });
+
+    // Render sprites
+    for sprite in sprites.iter() {
+        let distance_x: f64 = (sprite.x + 0.5) - origin_x;
+        let distance_y: f64 = (sprite.y + 0.5) - origin_y;
+
+        // The angle between the player and the sprite
+        let mut theta: f64 = f64::atan2(distance_y, distance_x);
+        theta = wrap_angle(theta);
+
+        // The angle between the player and the sprite, relative to the player rotation
+        let mut gamma: f64 = theta - rotation;
+        gamma = wrap_angle(gamma);
+
+        let sprite_distance: f64 = f64::sqrt(distance_x.powi(2) + distance_y.powi(2)) * f64::cos(rotation - theta);
+
+        // Sprites level with or behind the camera can't be seen
+        if sprite_distance <= 0.0 {
+            continue;
+        }
+
+        let lighting: f64 = calculate_lighting(sprite_distance, LIGHT_RADIUS);
+
+        // The number of pixels to offset from the center of the screen
+        let sprite_pixel_offset: f64 = f64::tan(gamma) * projection_distance;
+        let sprite_screen_x: i32 = f64::round((projection_width as f64 / 2.0) + sprite_pixel_offset) as i32;
+
+        let sprite_height: i32 = (f64::round(projection_distance / sprite_distance) as i32).wrapping_abs();
+        let sprite_width: i32 = (f64::round(projection_distance / sprite_distance) as i32).wrapping_abs();
+        if (sprite_height == 0) || (sprite_width == 0) {
+            continue;
+        }
+
+        // Sprites right up against the camera can be far bigger than the screen
+        let sprite_screen_start_x: i32 = sprite_screen_x.saturating_sub(sprite_width / 2);
+        let sprite_screen_end_x: i32 = sprite_screen_x.saturating_add(sprite_width / 2);
+        let sprite_screen_start_y: i32 = (projection_height as i32 / 2).saturating_sub(sprite_height / 2);
+        let sprite_screen_end_y: i32 = (projection_height as i32 / 2).saturating_add(sprite_height / 2);
+
+        let mut camera_min_angle: f64 = -field_of_view.to_radians() / 2.0;
+        camera_min_angle = wrap_angle(camera_min_angle);
+
+        let mut camera_max_angle: f64 = field_of_view.to_radians() / 2.0;
+        camera_max_angle = wrap_angle(camera_max_angle);
+
+        let texture: &Texture = match get_texture(textures, sprite.texture_id) {
+            Some(texture) => texture,
+            None => continue
+        };
+
+        for sprite_screen_row in sprite_screen_start_x.max(0)..sprite_screen_end_x.min(projection_width as i32) {
+            if (sprite_screen_row < 0) || (sprite_screen_row >= projection_width as i32) {
+                continue;
+            }
+
+            // If the sprite is not visible, don't render it.
+            if ((gamma < camera_min_angle) && (gamma > camera_max_angle)) ||
+                (framebuffer.depth_buffer[sprite_screen_row as usize] < sprite_distance) {
+                continue;
+            }
+
+            for sprite_screen_col in sprite_screen_start_y.max(0)..sprite_screen_end_y.min(projection_height as i32) {
+                if (sprite_screen_col < 0) || (sprite_screen_col >= projection_height as i32) {
+                    continue;
+                }
+
+                let sprite_row: i64 = sprite_screen_row as i64 - sprite_screen_start_x as i64;
+                let sprite_col: i64 = sprite_screen_col as i64 - sprite_screen_start_y as i64;
+
+                let texture_x: u32 = f64::round((sprite_row as f64 / sprite_width as f64) * (texture.width - 1) as f64) as u32;
+                let texture_y: u32 = f64::round((sprite_col as f64 / sprite_height as f64) * (texture.height - 1) as f64) as u32;
+
+                let mut color: Color = texture.get_pixel(texture_x, texture_y);
+                if color.a == 0 {
+                    continue;
+                }
+
+                color.r = (color.r as f64 * lighting) as u8;
+                color.g = (color.g as f64 * lighting) as u8;
+                color.b = (color.b as f64 * lighting) as u8;
+
+                framebuffer.set_pixel(sprite_screen_row as u32, sprite_screen_col as u32, color);
+            }
+        }
+    }
+}
+
+// The textures every frame needs, whatever the map looks like
+struct Surfaces<'a> {
+    wall: &'a Texture,
+    ceiling: &'a Texture,
+    floor: &'a Texture
+}
+
+// Where a column's ray ended up, and how its wall strip is drawn
+struct Column<'a> {
+    ray_angle: f64,
+    wall_texture: &'a Texture,
+    wall_texture_x: u32,
+    is_wall_shaded: bool,
+    line_height: i32,
+    line_screen_start: i32,
+    line_screen_end: i32,
+    wall_lighting: f64
+}
+
+// Casts the ray for every column in the band, which starts at first_column on screen, and fills in its depth
+fn cast_columns<'a>(depth_buffer: &mut [f64], first_column: u32, camera: &Camera, map: &Map, textures: &'a HashMap<u32, Texture>, surfaces: &Surfaces<'a>) -> Vec<Column<'a>> {
+    let projection_width: u32 = camera.projection_width;
+    let projection_height: u32 = camera.projection_height;
+    let projection_distance: f64 = camera.projection_distance;
+    let rotation: f64 = camera.rotation;
+
+    let mut columns: Vec<Column> = Vec::with_capacity(depth_buffer.len());
+
+    // Raycasting
+    for (x, depth) in depth_buffer.iter_mut().enumerate() {
+        // The vertical stripe that this ray is going through
+        let ray_screen_x: f64 = -(projection_width as f64) / 2.0 + (first_column as usize + x) as f64;
+
+        // The distance from the viewer to the stripe on the screen;
+        let ray_view_dist = (ray_screen_x.powi(2) + projection_distance.powi(2)).sqrt();
+
+        // Calculate the angle of the ray and cast it
+        let ray_angle: f64 = (ray_screen_x / ray_view_dist).asin() + rotation;
+        let intersection: Option<RaycastHit> = map.raycast(camera.origin_x, camera.origin_y, ray_angle, MAX_RAY_DISTANCE);
+
+        // Calculate the actual distance (rays that miss are treated as ending at the max distance)
+        let ray_distance: f64 = intersection.as_ref().map_or(MAX_RAY_DISTANCE, |hit| hit.distance);
+        let intersection_distance = ray_distance * (rotation - ray_angle).cos();
+        *depth = intersection_distance;
+
+        // Each face of each tile can have its own texture
+        let wall_texture: &Texture = intersection.as_ref()
+            .and_then(|hit| get_texture(textures, hit.texture_id))
+            .unwrap_or(surfaces.wall);
+
+        // Walls facing north or south are drawn darker
+        let is_wall_shaded: bool = intersection.as_ref().map_or(false, |hit| hit.face.is_horizontal());
+
+        // Calculate the x texel of this wall strip
+        let wall_texture_x: u32 = match intersection {
+            Some(ref hit) if !hit.face.is_horizontal() => {
+                (((hit.y - (hit.tile_y as f64 * TILE_WIDTH)) % TILE_WIDTH) * (wall_texture.width - 1) as f64).round() as u32
+            },
+            Some(ref hit) => {
+                (((hit.x - (hit.tile_x as f64 * TILE_WIDTH)) % TILE_WIDTH) * (wall_texture.width - 1) as f64).round() as u32
+            },
+            None => 0
+        };
+
+        // Calculate the values for the wall strip
+        let line_height: i32 = if intersection.is_some() {
+            ((TILE_HEIGHT * projection_distance) / intersection_distance).round() as i32
+        } else {
+            0
+        };
+        let line_screen_start: i32 = (projection_height as i32 / 2) - (line_height / 2);
+
+        columns.push(Column {
+            ray_angle: ray_angle,
+            wall_texture: wall_texture,
+            wall_texture_x: wall_texture_x,
+            is_wall_shaded: is_wall_shaded,
+            line_height: line_height,
+            line_screen_start: line_screen_start,
+            line_screen_end: line_screen_start + line_height,
+            wall_lighting: calculate_lighting(intersection_distance, LIGHT_RADIUS)
+        });
+    }
+
+    columns
+}
+
+// Draws the walls, floors and ceilings of every row in the band, which starts at first_row on screen
+fn draw_rows(pixels: &mut [u8], first_row: u32, camera: &Camera, columns: &[Column], surfaces: &Surfaces) {
+    let projection_height: u32 = camera.projection_height;
+    let projection_distance: f64 = camera.projection_distance;
+    let origin_x: f64 = camera.origin_x;
+    let origin_y: f64 = camera.origin_y;
+    let rotation: f64 = camera.rotation;
+    let (ceiling_texture, floor_texture): (&Texture, &Texture) = (surfaces.ceiling, surfaces.floor);
+
+    for (row, row_pixels) in pixels.chunks_mut(camera.projection_width as usize * 4).enumerate() {
+        let y: u32 = first_row + row as u32;
+
+        for (column, pixel) in columns.iter().zip(row_pixels.chunks_mut(4)) {
+            let ray_angle: f64 = column.ray_angle;
+            let line_screen_start: i32 = column.line_screen_start;
+            let line_screen_end: i32 = column.line_screen_end;
+
+            // Walls
+            if ((y as i32) >= line_screen_start) && ((y as i32) < line_screen_end) {
+                let wall_texture: &Texture = column.wall_texture;
+                let wall_lighting: f64 = column.wall_lighting;
+                let is_wall_shaded: bool = column.is_wall_shaded;
+
+                let line_y: i32 = y as i32 - line_screen_start;
+                let texture_y: u32 = f64::floor((line_y as f64 / column.line_height as f64) * (wall_texture.height - 1) as f64) as u32;
+
+                let mut color: Color = wall_texture.get_pixel(column.wall_texture_x, texture_y);
+                color.r = ((if is_wall_shaded { color.r / 2 } else { color.r }) as f64 * wall_lighting) as u8;
+                color.g = ((if is_wall_shaded { color.g / 2 } else { color.g }) as f64 * wall_lighting) as u8;
+                color.b = ((if is_wall_shaded { color.b / 2 } else { color.b }) as f64 * wall_lighting) as u8;
+
+                put_pixel(pixel, color);
+                continue;
+            }
+
+            // Floors
+            if (y as i32) >= line_screen_end {
+                let floor_row: i32 = (y as i32) - (projection_height as i32 / 2);
+
+                // The horizon is infinitely far away, so no light reaches it
+                if floor_row == 0 {
+                    put_pixel(pixel, Color::RGB(0, 0, 0));
+                    continue;
+                }
+
+                let floor_straight_distance = (PLAYER_HEIGHT / floor_row as f64) * projection_distance;
+                let angle_beta_radians = rotation - ray_angle;
+
+                let floor_actual_distance = floor_straight_distance / angle_beta_radians.cos();
+
+                let mut floor_hit_x: f64 = origin_x + (floor_actual_distance * ray_angle.cos());
+                let mut floor_hit_y: f64 = origin_y + (floor_actual_distance * ray_angle.sin());
+
+                floor_hit_x -= floor_hit_x.floor();
+                floor_hit_y -= floor_hit_y.floor();
+
+                let texture_x: u32 = f64::floor(floor_hit_x * (floor_texture.width - 1) as f64) as u32;
+                let texture_y: u32 = f64::floor(floor_hit_y * (floor_texture.height - 1) as f64) as u32;
+
+                let floor_lighting: f64 = calculate_lighting(floor_straight_distance, LIGHT_RADIUS);
+                let mut color: Color = floor_texture.get_pixel(texture_x, texture_y);
+                color.r = (color.r as f64 * floor_lighting) as u8;
+                color.g = (color.g as f64 * floor_lighting) as u8;
+                color.b = (color.b as f64 * floor_lighting) as u8;
+
+                put_pixel(pixel, color);
+                continue;
+            }
+
+            // Ceilings
+            if (y as i32) < line_screen_start {
+                let ceiling_row: i32 = (y as i32) - (projection_height as i32 / 2);
+
+                if ceiling_row == 0 {
+                    put_pixel(pixel, Color::RGB(0, 0, 0));
+                    continue;
+                }
+
+                let ceiling_straight_distance = (PLAYER_HEIGHT / ceiling_row as f64) * projection_distance;
+                let angle_beta_radians = rotation - ray_angle;
+
+                let ceiling_actual_distance = ceiling_straight_distance / angle_beta_radians.cos();
+
+                let mut ceiling_hit_x: f64 = origin_x - (ceiling_actual_distance * ray_angle.cos());
+                let mut ceiling_hit_y: f64 = origin_y - (ceiling_actual_distance * ray_angle.sin());
+
+                ceiling_hit_x -= ceiling_hit_x.floor();
+                ceiling_hit_y -= ceiling_hit_y.floor();
+
+                let texture_x: u32 = f64::floor(ceiling_hit_x * (ceiling_texture.width - 1) as f64) as u32;
+                let texture_y: u32 = f64::floor(ceiling_hit_y * (ceiling_texture.height - 1) as f64) as u32;
+
+                let ceiling_lighting: f64 = calculate_lighting(ceiling_straight_distance.abs(), LIGHT_RADIUS);
+                let mut color: Color = ceiling_texture.get_pixel(texture_x, texture_y);
+                color.r = (color.r as f64 * ceiling_lighting) as u8;
+                color.g = (color.g as f64 * ceiling_lighting) as u8;
+                color.b = (color.b as f64 * ceiling_lighting) as u8;
+
+                put_pixel(pixel, color);
+                continue;
+            }
+        }
+    }
+}
+
+fn put_pixel(pixel: &mut [u8], color: Color) {
+    pixel[0] = color.r;
+    pixel[1] = color.g;
+    pixel[2] = color.b;
+    pixel[3] = color.a;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use sdl2::pixels::Color;
+    use generator::{generate, GeneratorSettings};
+    use map::{Map, Tile};
+    use texture::Texture;
+    use super::{Framebuffer, View, render_world};
+
+    // A different pattern for every texture, so a wrong texel or texture shows up in the output
+    fn test_textures(map: &Map) -> HashMap<u32, Texture> {
+        let mut textures: HashMap<u32, Texture> = HashMap::new();
+
+        for id in 0..map.texture_names.len() as u32 {
+            let mut pixels: Vec<Color> = Vec::new();
+            for y in 0..16 {
+                for x in 0..16 {
+                    let alpha: u8 = if (id > 3) && ((x + y) % 5 == 0) { 0 } else { 255 };
+                    pixels.push(Color::RGBA((id * 40 + x * 8) as u8, (y * 16) as u8, ((x ^ y) * 8) as u8, alpha));
+                }
+            }
+
+            textures.insert(id, Texture::new(16, 16, pixels));
+        }
+
+        textures
+    }
+
+    fn render(map: &Map, textures: &HashMap<u32, Texture>, view: &View, thread_count: usize) -> Framebuffer {
+        // Odd sizes, so the bands don't all come out the same size
+        let mut framebuffer: Framebuffer = Framebuffer::new(157, 91);
+        render_world(&mut framebuffer, map, textures, view, thread_count);
+        framebuffer
+    }
+
+    #[test]
+    fn output_does_not_depend_on_thread_count() {
+        let map: Map = generate(&GeneratorSettings { seed: 42, ..GeneratorSettings::default() }).unwrap();
+        let textures: HashMap<u32, Texture> = test_textures(&map);
+
+        for step in 0..8 {
+            let view = View {
+                origin_x: map.spawn.x as f64 + 0.5,
+                origin_y: map.spawn.y as f64 + 0.5,
+                rotation: step as f64 * 0.8,
+                field_of_view: 75.0,
+                interpolation: 1.0
+            };
+
+            let single: Framebuffer = render(&map, &textures, &view, 1);
+            for &thread_count in [2, 3, 4, 7, 16].iter() {
+                let banded: Framebuffer = render(&map, &textures, &view, thread_count);
+                assert!(single.pixels == banded.pixels, "{} threads rendered differently at rotation step {}", thread_count, step);
+                assert!(single.depth_buffer == banded.depth_buffer);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_textures_are_left_out() {
+        let map: Map = generate(&GeneratorSettings { seed: 42, ..GeneratorSettings::default() }).unwrap();
+        let mut textures: HashMap<u32, Texture> = test_textures(&map);
+        for id in 3..map.texture_names.len() as u32 {
+            textures.insert(id, Texture::new(0, 0, Vec::new()));
+        }
+
+        let view = View {
+            origin_x: map.spawn.x as f64 + 0.5,
+            origin_y: map.spawn.y as f64 + 0.5,
+            rotation: 0.0,
+            field_of_view: 75.0,
+            interpolation: 1.0
+        };
+
+        render(&map, &textures, &view, 1);
+    }
+
+    #[test]
+    fn horizon_is_dark() {
+        // No walls anywhere, so every ray misses and the floor meets the ceiling in the middle of the screen
+        let map: Map = Map::new(3, 3, vec![None::<Tile>; 9], Vec::new());
+        let textures: HashMap<u32, Texture> = test_textures(&map);
+
+        let view = View {
+            origin_x: 1.5,
+            origin_y: 1.5,
+            rotation: 0.0,
+            field_of_view: 75.0,
+            interpolation: 1.0
+        };
+
+        let framebuffer: Framebuffer = render(&map, &textures, &view, 1);
+        for x in 0..framebuffer.width {
+            assert_eq!(framebuffer.get_pixel(x, framebuffer.height / 2), Color::RGB(0, 0, 0));
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use toml;
+use campaign::Campaign;
+use error;
+use error::Error;
+use simulation;
+use simulation::{TickInput, RunResult, TICK_SECONDS};
+
+// Bumped whenever the simulation changes in a way that makes old recordings play out differently
+pub const RECORDING_VERSION: u32 = 1;
+
+// A recorded run of a campaign: the levels it was played on (map paths, generator seeds and
+// difficulty settings), and what the player did on every tick. Mouse, key and controller settings
+// are already worked into the inputs, so they don't need recording.
+// Maps loaded from files have to be unchanged for a replay to match.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Recording {
+    pub version: u32,
+    pub tick_seconds: f64,
+    pub campaign: Campaign,
+    // Where the run ended up, once it's over
+    #[serde(default)]
+    pub result: Option<RunResult>,
+    // Inputs are stored as runs of identical ticks, since most ticks repeat the one before
+    #[serde(default)]
+    pub inputs: Vec<InputRun>
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct InputRun {
+    pub ticks: u32,
+    #[serde(flatten)]
+    pub input: TickInput
+}
+
+impl Recording {
+    pub fn new(campaign: Campaign) -> Recording {
+        Recording {
+            version: RECORDING_VERSION,
+            tick_seconds: TICK_SECONDS,
+            campaign: campaign,
+            result: None,
+            inputs: Vec::new()
+        }
+    }
+
+    pub fn push(&mut self, input: TickInput) {
+        if let Some(last) = self.inputs.last_mut() {
+            if last.input == input {
+                last.ticks += 1;
+                return;
+            }
+        }
+
+        self.inputs.push(InputRun {
+            ticks: 1,
+            input: input
+        });
+    }
+
+    pub fn tick_count(&self) -> u64 {
+        self.inputs.iter().map(|run| run.ticks as u64).sum()
+    }
+
+    pub fn load(file_path: &str) -> error::Result<Recording> {
+        let mut contents: String = String::new();
+        File::open(file_path)?.read_to_string(&mut contents)?;
+
+        let recording: Recording = toml::from_str(&contents)
+            .map_err(|e| Error::Corrupted(format!("Invalid recording {}: {}", file_path, e)))?;
+
+        if recording.version != RECORDING_VERSION {
+            return Err(Error::Corrupted(format!("Recording {} is version {}, but only version {} can be replayed", file_path, recording.version, RECORDING_VERSION)));
+        }
+
+        if recording.tick_seconds != TICK_SECONDS {
+            return Err(Error::Corrupted(format!("Recording {} was made at a different tick rate", file_path)));
+        }
+
+        Ok(recording)
+    }
+
+    pub fn save(&self, file_path: &str) -> io::Result<()> {
+        let contents: String = toml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        File::create(file_path)?.write_all(contents.as_bytes())
+    }
+}
+
+// Hands out a recording's inputs one tick at a time
+pub struct Playback {
+    pub recording: Recording,
+    run_index: usize,
+    run_tick: u32
+}
+
+impl Playback {
+    pub fn new(recording: Recording) -> Playback {
+        Playback {
+            recording: recording,
+            run_index: 0,
+            run_tick: 0
+        }
+    }
+}
+
+impl Iterator for Playback {
+    type Item = TickInput;
+
+    // The input for the next tick, or None once the recording runs out
+    fn next(&mut self) -> Option<TickInput> {
+        while self.run_index < self.recording.inputs.len() {
+            let run: InputRun = self.recording.inputs[self.run_index];
+            if self.run_tick < run.ticks {
+                self.run_tick += 1;
+                return Some(run.input);
+            }
+
+            self.run_index += 1;
+            self.run_tick = 0;
+        }
+
+        None
+    }
+}
+
+// Plays a recording back as fast as possible without a window, returning where it ended up
+pub fn replay_headless(recording: &Recording) -> error::Result<RunResult> {
+    simulation::run_headless(recording.campaign.clone(), Playback::new(recording.clone()), recording.tick_count())
+}
+
+// Prints whether a replay ended up where the recording did
+pub fn report(recording: &Recording, result: &RunResult) {
+    match recording.result {
+        Some(ref expected) if expected == result => {
+            println!("Replay matches the recording: {:?}", result);
+        },
+        Some(ref expected) => {
+            println!("Replay does not match the recording!");
+            println!("  Recorded: {:?}", expected);
+            println!("  Replayed: {:?}", result);
+        },
+        None => {
+            println!("Replay finished (the recording has no result to compare against): {:?}", result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml;
+    use campaign::Campaign;
+    use generator::GeneratorSettings;
+    use random::Random;
+    use simulation::{Simulation, SimEvent, TickInput, RunResult};
+    use super::{Recording, replay_headless};
+
+    // Plays a run the way the game does, with the player wandering about at random, and records it
+    fn record(seed: u64) -> Recording {
+        let campaign: Campaign = Campaign::generated(GeneratorSettings {
+            seed: seed,
+            ..GeneratorSettings::default()
+        });
+
+        let mut recording: Recording = Recording::new(campaign.clone());
+        let mut simulation: Simulation = Simulation::new(campaign);
+        simulation.start().unwrap();
+
+        let mut random: Random = Random::new(seed);
+        let mut input: TickInput = TickInput::default();
+        for tick in 0..3600 {
+            // Players hold keys down for a while rather than changing them every tick
+            if tick % 20 == 0 {
+                input = TickInput {
+                    forward: (random.next_f64() * 2.0) - 1.0,
+                    strafe: (random.next_f64() * 2.0) - 1.0,
+                    turn: (random.next_f64() - 0.5) * 0.1
+                };
+            }
+
+            recording.push(input);
+            let events: Vec<SimEvent> = simulation.tick(&input).unwrap();
+            if events.contains(&SimEvent::Died) || events.contains(&SimEvent::Victory) {
+                break;
+            }
+        }
+
+        recording.result = Some(simulation.result());
+        recording
+    }
+
+    #[test]
+    fn replays_end_up_where_the_recording_did() {
+        for seed in 1..4 {
+            // Through the file format and back, like a recording saved to disk
+            let contents: String = toml::to_string(&record(seed)).unwrap();
+            let recording: Recording = toml::from_str(&contents).unwrap();
+
+            let result: RunResult = replay_headless(&recording).unwrap();
+            assert_eq!(Some(result), recording.result, "seed {} replayed differently", seed);
+        }
+    }
+}
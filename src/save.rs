@@ -0,0 +1,377 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use time;
+use toml;
+use campaign::{Campaign, Level};
+use entity::{Archetype, Entity};
+use error;
+use error::Error;
+use map::{Map, Position};
+use simulation;
+use simulation::Simulation;
+use SAVES_DIR;
+
+// How many save slots there are
+pub const SAVE_SLOTS: usize = 3;
+
+// Bumped whenever the save format changes, with a step added to MIGRATIONS to upgrade older saves
+pub const SAVE_VERSION: u32 = 2;
+
+// MIGRATIONS[n] upgrades a save from version n + 1 to version n + 2
+const MIGRATIONS: [fn(&mut toml::value::Table); 1] = [texture_ids_to_names];
+
+// Version 1 saved entity textures by the ids the game used to have built in,
+// before textures were named in the asset manifest
+fn texture_ids_to_names(table: &mut toml::value::Table) {
+    const TEXTURE_NAMES: [&'static str; 11] = ["wall", "ceiling", "floor", "schindler", "treasure", "monster", "barrel", "statue", "gravestone", "stone_schindler", "exit"];
+
+    let entities = match table.get_mut("entity").and_then(|entities| entities.as_array_mut()) {
+        Some(entities) => entities,
+        None => return
+    };
+
+    for entity in entities.iter_mut().filter_map(|entity| entity.as_table_mut()) {
+        let name: &str = entity.remove("texture_id")
+            .and_then(|id| id.as_integer())
+            .and_then(|id| TEXTURE_NAMES.get(id as usize).cloned())
+            .unwrap_or("");
+
+        entity.insert("texture".to_string(), toml::Value::String(name.to_string()));
+    }
+}
+
+// A run of the campaign, frozen part way through.
+// The map itself is loaded again from the campaign level, only the entities on it are saved.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SaveGame {
+    pub version: u32,
+    // When the game was saved, as YYYY-MM-DD HH:MM
+    pub saved_at: String,
+
+    pub level_index: usize,
+    // Used to check the level's map hasn't changed since the game was saved
+    pub map_name: String,
+    pub map_width: u32,
+    pub map_height: u32,
+
+    pub player_x: f64,
+    pub player_y: f64,
+    pub player_rotation: f64,
+    pub score: u32,
+    pub difficulty: u32,
+
+    pub level_treasure_goal: u32,
+    pub level_treasure_collected: u32,
+    pub levels_completed: u32,
+    pub is_exit_open: bool,
+    pub ticks: u64,
+    pub play_time: f64,
+    pub pathfind_timer: f64,
+    pub difficulty_timer: f64,
+
+    pub campaign: Campaign,
+    #[serde(default, rename = "entity")]
+    pub entities: Vec<SavedEntity>
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SavedEntity {
+    pub id: u32,
+    pub x: f64,
+    pub y: f64,
+    pub destination_x: i32,
+    pub destination_y: i32,
+    pub texture: String,
+    pub archetype: Archetype,
+    pub follow_player: bool,
+    pub solid: bool,
+    pub is_deleted: bool
+}
+
+// Anything off the map would leave the chasers' pathfinding with nowhere sensible to go
+fn is_on_map(map: &Map, x: f64, y: f64) -> bool {
+    x.is_finite() && y.is_finite() && map.is_inside(x.floor() as i32, y.floor() as i32)
+}
+
+pub fn slot_path(slot: usize) -> PathBuf {
+    PathBuf::from(SAVES_DIR).join(format!("slot{}.toml", slot + 1))
+}
+
+impl SaveGame {
+    pub fn capture(simulation: &Simulation) -> SaveGame {
+        let entities: Vec<SavedEntity> = simulation.map.entities.iter()
+            .map(|ent| SavedEntity {
+                id: ent.id,
+                x: ent.x,
+                y: ent.y,
+                destination_x: ent.destination.x,
+                destination_y: ent.destination.y,
+                texture: simulation.map.texture_name(ent.texture_id).unwrap_or("").to_string(),
+                archetype: ent.archetype,
+                follow_player: ent.follow_player,
+                solid: ent.solid,
+                is_deleted: ent.is_deleted
+            })
+            .collect();
+
+        SaveGame {
+            version: SAVE_VERSION,
+            saved_at: time::strftime("%Y-%m-%d %H:%M", &time::now()).unwrap_or_default(),
+            level_index: simulation.level_index,
+            map_name: simulation.map.info.name.clone(),
+            map_width: simulation.map.width,
+            map_height: simulation.map.height,
+            player_x: simulation.player_x,
+            player_y: simulation.player_y,
+            player_rotation: simulation.player_rotation,
+            score: simulation.score,
+            difficulty: simulation.difficulty,
+            level_treasure_goal: simulation.level_treasure_goal,
+            level_treasure_collected: simulation.level_treasure_collected,
+            levels_completed: simulation.levels_completed,
+            is_exit_open: simulation.is_exit_open,
+            ticks: simulation.ticks,
+            play_time: simulation.play_time,
+            pathfind_timer: simulation.pathfind_timer,
+            difficulty_timer: simulation.difficulty_timer,
+            campaign: simulation.campaign.clone(),
+            entities: entities
+        }
+    }
+
+    // Loads the level's map again and puts everything back where it was
+    pub fn restore(&self) -> error::Result<Simulation> {
+        let mismatch = |message: String| Error::Corrupted(format!("The save doesn't match its map: {}", message));
+
+        let level: Level = self.campaign.levels.get(self.level_index).cloned()
+            .ok_or_else(|| mismatch(format!("the campaign has no level {}", self.level_index + 1)))?;
+
+        let mut map: Map = simulation::load_map(&level)
+            .map_err(|e| mismatch(format!("failed to load {}: {}", level.describe(), e)))?;
+
+        if (map.width != self.map_width) || (map.height != self.map_height) || (map.info.name != self.map_name) {
+            return Err(mismatch(format!("{} has changed since the game was saved", level.describe())));
+        }
+
+        if !is_on_map(&map, self.player_x, self.player_y) {
+            return Err(mismatch(format!("the player is off the map at {}, {}", self.player_x, self.player_y)));
+        }
+
+        if map.is_solid(self.player_x.trunc() as i32, self.player_y.trunc() as i32) {
+            return Err(mismatch("the player is inside a wall".to_string()));
+        }
+
+        let mut entities: Vec<Entity> = Vec::new();
+        for saved in self.entities.iter() {
+            if !is_on_map(&map, saved.x, saved.y) || !map.is_inside(saved.destination_x, saved.destination_y) {
+                return Err(mismatch(format!("entity {} is off the map", saved.id)));
+            }
+
+            let texture_id: u32 = map.texture_id(&saved.texture);
+            let mut ent: Entity = Entity::new(saved.id, saved.x, saved.y, texture_id, saved.archetype, saved.solid);
+            ent.destination = Position::new(saved.destination_x, saved.destination_y);
+            ent.follow_player = saved.follow_player;
+            ent.is_deleted = saved.is_deleted;
+            entities.push(ent);
+        }
+        map.entities = entities;
+
+        let mut simulation: Simulation = Simulation::new(self.campaign.clone());
+        simulation.map = map;
+        simulation.level_index = self.level_index;
+        simulation.player_x = self.player_x;
+        simulation.player_y = self.player_y;
+        simulation.player_rotation = self.player_rotation;
+        simulation.previous_player_x = self.player_x;
+        simulation.previous_player_y = self.player_y;
+        simulation.previous_player_rotation = self.player_rotation;
+        simulation.score = self.score;
+        simulation.difficulty = self.difficulty;
+        simulation.level_treasure_goal = self.level_treasure_goal;
+        simulation.level_treasure_collected = self.level_treasure_collected;
+        simulation.levels_completed = self.levels_completed;
+        simulation.is_exit_open = self.is_exit_open;
+        simulation.has_left_spawn = !simulation.is_on_spawn();
+        simulation.ticks = self.ticks;
+        simulation.play_time = self.play_time;
+        simulation.pathfind_timer = self.pathfind_timer;
+        simulation.difficulty_timer = self.difficulty_timer;
+
+        Ok(simulation)
+    }
+
+    // Loads the save in a slot, upgrading it if it was made by an older version.
+    // Fails with NotFound if the slot is empty.
+    pub fn load(slot: usize) -> error::Result<SaveGame> {
+        let file_path: PathBuf = slot_path(slot);
+        let mut contents: String = String::new();
+        File::open(&file_path)?.read_to_string(&mut contents)?;
+
+        SaveGame::parse(&contents, &file_path.display().to_string())
+    }
+
+    // Reads a save from the contents of its file, upgrading it if it was made by an older version
+    pub fn parse(contents: &str, file_path: &str) -> error::Result<SaveGame> {
+        let corrupted = |message: String| Error::Corrupted(format!("Save {} is corrupted: {}", file_path, message));
+
+        let mut table: toml::value::Table = toml::from_str(contents)
+            .map_err(|e| corrupted(e.to_string()))?;
+
+        let version: u32 = match table.get("version").and_then(|version| version.as_integer()) {
+            Some(version) if version >= 1 => version as u32,
+            _ => return Err(corrupted("it has no version".to_string()))
+        };
+
+        if version > SAVE_VERSION {
+            return Err(Error::Corrupted(format!("Save {} is version {}, which is newer than this game understands (version {})", file_path, version, SAVE_VERSION)));
+        }
+
+        for from in version..SAVE_VERSION {
+            MIGRATIONS[(from - 1) as usize](&mut table);
+        }
+        table.insert("version".to_string(), toml::Value::Integer(SAVE_VERSION as i64));
+
+        toml::Value::Table(table).try_into()
+            .map_err(|e| corrupted(e.to_string()))
+    }
+
+    pub fn save(&self, slot: usize) -> io::Result<()> {
+        let file_path: PathBuf = slot_path(slot);
+        let contents: String = toml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to write save {}: {}", file_path.display(), e)))?;
+
+        fs::create_dir_all(SAVES_DIR)?;
+        File::create(&file_path)?.write_all(contents.as_bytes())
+    }
+
+    // A line describing the save, for picking a slot
+    pub fn describe(&self) -> String {
+        format!("{}  {}  score {}", self.saved_at, self.map_name, self.score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml;
+    use campaign::Campaign;
+    use error::Error;
+    use generator::GeneratorSettings;
+    use simulation::{Simulation, TickInput};
+    use super::{SaveGame, texture_ids_to_names};
+
+    fn simulation() -> Simulation {
+        let mut simulation: Simulation = Simulation::new(Campaign::generated(GeneratorSettings { seed: 11, ..GeneratorSettings::default() }));
+        simulation.start().unwrap();
+
+        // Far enough in for the chasers to have moved
+        for _ in 0..90 {
+            simulation.tick(&TickInput::default()).unwrap();
+        }
+
+        simulation
+    }
+
+    fn is_corrupted(result: Result<SaveGame, Error>) -> bool {
+        matches!(result, Err(Error::Corrupted(_)))
+    }
+
+    // The save as version 1 wrote it, with entity textures as the ids the game used to have built in
+    fn as_version_1(save: &SaveGame) -> String {
+        const TEXTURE_NAMES: [&'static str; 11] = ["wall", "ceiling", "floor", "schindler", "treasure", "monster", "barrel", "statue", "gravestone", "stone_schindler", "exit"];
+
+        let mut table: toml::value::Table = toml::Value::try_from(save).unwrap().try_into().unwrap();
+        table.insert("version".to_string(), toml::Value::Integer(1));
+
+        for entity in table.get_mut("entity").unwrap().as_array_mut().unwrap().iter_mut() {
+            let entity: &mut toml::value::Table = entity.as_table_mut().unwrap();
+            let name: String = entity.remove("texture").unwrap().as_str().unwrap().to_string();
+            let id: usize = TEXTURE_NAMES.iter().position(|texture_name| *texture_name == name).unwrap();
+            entity.insert("texture_id".to_string(), toml::Value::Integer(id as i64));
+        }
+
+        toml::to_string(&toml::Value::Table(table)).unwrap()
+    }
+
+    #[test]
+    fn texture_ids_become_names() {
+        let mut table: toml::value::Table = toml::from_str("
+[[entity]]
+texture_id = 4
+
+[[entity]]
+texture_id = 99
+").unwrap();
+
+        texture_ids_to_names(&mut table);
+
+        let entities = table["entity"].as_array().unwrap();
+        assert_eq!(entities[0].get("texture").and_then(|texture| texture.as_str()), Some("treasure"));
+        assert_eq!(entities[1].get("texture").and_then(|texture| texture.as_str()), Some(""));
+        assert!(entities[0].get("texture_id").is_none());
+    }
+
+    #[test]
+    fn saves_restore_where_they_left_off() {
+        let simulation: Simulation = simulation();
+        let save: SaveGame = SaveGame::capture(&simulation);
+
+        let loaded: SaveGame = SaveGame::parse(&toml::to_string(&save).unwrap(), "test").unwrap();
+        let restored: Simulation = loaded.restore().unwrap();
+
+        assert_eq!(restored.result(), simulation.result());
+        assert_eq!(restored.map.entities.len(), simulation.map.entities.len());
+        for (restored, original) in restored.map.entities.iter().zip(simulation.map.entities.iter()) {
+            assert_eq!((restored.x, restored.y, restored.texture_id), (original.x, original.y, original.texture_id));
+        }
+    }
+
+    #[test]
+    fn version_1_saves_are_upgraded() {
+        let simulation: Simulation = simulation();
+        let save: SaveGame = SaveGame::capture(&simulation);
+
+        let upgraded: SaveGame = SaveGame::parse(&as_version_1(&save), "test").unwrap();
+        assert_eq!(upgraded.version, super::SAVE_VERSION);
+
+        let textures: Vec<&str> = upgraded.entities.iter().map(|ent| ent.texture.as_str()).collect();
+        let expected: Vec<&str> = save.entities.iter().map(|ent| ent.texture.as_str()).collect();
+        assert_eq!(textures, expected);
+
+        let restored: Simulation = upgraded.restore().unwrap();
+        assert_eq!(restored.result(), simulation.result());
+    }
+
+    #[test]
+    fn rejects_corrupted_saves() {
+        let contents: String = toml::to_string(&SaveGame::capture(&simulation())).unwrap();
+
+        assert!(is_corrupted(SaveGame::parse("this isn't a save", "test")));
+        assert!(is_corrupted(SaveGame::parse(&contents.replace("version = 2", ""), "test")));
+        assert!(is_corrupted(SaveGame::parse(&contents.replace("version = 2", "version = 3"), "test")));
+        assert!(is_corrupted(SaveGame::parse(&contents.replace("score = ", "points = "), "test")));
+    }
+
+    #[test]
+    fn rejects_saves_that_dont_match_their_map() {
+        let save: SaveGame = SaveGame::capture(&simulation());
+
+        let mut resized: SaveGame = save.clone();
+        resized.map_width += 2;
+        assert!(resized.restore().is_err());
+
+        let mut player_off_map: SaveGame = save.clone();
+        player_off_map.player_x = -3.5;
+        assert!(player_off_map.restore().is_err());
+
+        let mut entity_off_map: SaveGame = save.clone();
+        entity_off_map.entities[0].y = save.map_height as f64 + 10.0;
+        assert!(entity_off_map.restore().is_err());
+
+        let mut destination_off_map: SaveGame = save.clone();
+        destination_off_map.entities[0].destination_x = -1;
+        assert!(destination_off_map.restore().is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use toml;
+use error;
+use error::Error;
+
+// How many scores each leaderboard keeps
+pub const LEADERBOARD_SIZE: usize = 10;
+
+// The longest name that can be entered for a high score
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    // The difficulty reached before the run ended
+    pub difficulty: u32,
+    // Seconds survived
+    pub survival_time: f64,
+    // The map the run ended on
+    pub map: String,
+    // When the score was set, as YYYY-MM-DD
+    pub date: String
+}
+
+// The best scores for one campaign. Runs on different maps or seeds get different keys,
+// since their scores can't be compared.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Leaderboard {
+    pub key: String,
+    #[serde(default, rename = "score")]
+    pub scores: Vec<HighScore>
+}
+
+// Every leaderboard, saved between games
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HighScores {
+    #[serde(default, rename = "leaderboard")]
+    pub leaderboards: Vec<Leaderboard>
+}
+
+impl HighScores {
+    pub fn load(file_path: &Path) -> error::Result<HighScores> {
+        let mut contents: String = String::new();
+        File::open(file_path)?.read_to_string(&mut contents)?;
+
+        toml::from_str(&contents)
+            .map_err(|e| Error::Corrupted(format!("Invalid high scores {}: {}", file_path.display(), e)))
+    }
+
+    pub fn load_or_default(file_path: &Path) -> error::Result<HighScores> {
+        if !file_path.exists() {
+            return Ok(HighScores::default());
+        }
+
+        HighScores::load(file_path)
+    }
+
+    pub fn save(&self, file_path: &Path) -> io::Result<()> {
+        let contents: String = toml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to write high scores {}: {}", file_path.display(), e)))?;
+
+        File::create(file_path)?.write_all(contents.as_bytes())
+    }
+
+    // The scores for the given key, best first
+    pub fn scores(&self, key: &str) -> &[HighScore] {
+        match self.leaderboards.iter().find(|leaderboard| leaderboard.key == key) {
+            Some(leaderboard) => &leaderboard.scores,
+            None => &[]
+        }
+    }
+
+    // Whether a score would make it onto the leaderboard
+    pub fn qualifies(&self, key: &str, score: u32) -> bool {
+        let scores: &[HighScore] = self.scores(key);
+        (scores.len() < LEADERBOARD_SIZE) || scores.iter().any(|high_score| score > high_score.score)
+    }
+
+    // Adds a score to its leaderboard, returning its place (from 0),
+    // or None if it wasn't good enough. Ties go to whoever got the score first.
+    pub fn add(&mut self, key: &str, high_score: HighScore) -> Option<usize> {
+        if !self.leaderboards.iter().any(|leaderboard| leaderboard.key == key) {
+            self.leaderboards.push(Leaderboard {
+                key: key.to_string(),
+                scores: Vec::new()
+            });
+        }
+
+        let leaderboard: &mut Leaderboard = self.leaderboards.iter_mut().find(|leaderboard| leaderboard.key == key).unwrap();
+
+        let place: usize = leaderboard.scores.iter()
+            .position(|other| high_score.score > other.score)
+            .unwrap_or(leaderboard.scores.len());
+
+        if place >= LEADERBOARD_SIZE {
+            return None;
+        }
+
+        leaderboard.scores.insert(place, high_score);
+        leaderboard.scores.truncate(LEADERBOARD_SIZE);
+
+        Some(place)
+    }
+}
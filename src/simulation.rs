@@ -0,0 +1,395 @@
+use map::{Map, Position, Spawn};
+use entity::Archetype;
+use campaign::{Campaign, Level};
+use generator;
+use error;
+use error::Error;
+use {wrap_angle, TEXTURE_EXIT};
+
+// The game simulates in fixed steps of TICK_SECONDS, no matter how fast it renders.
+// Nothing in here looks at the clock, so the same inputs always play out the same way.
+pub const TICK_SECONDS: f64 = 1.0 / 60.0;
+
+// Seconds between chasers finding a new path to the player
+const PATHFIND_INTERVAL: f64 = 0.25;
+
+// How the player moves on one tick, already worked out from the keys, mouse and sticks.
+// forward and strafe go from -1 to 1, turn is in radians.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct TickInput {
+    pub forward: f64,
+    pub strafe: f64,
+    pub turn: f64
+}
+
+// Things that happened during a tick, for the game to play sounds and change screens on
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SimEvent {
+    TreasureCollected,
+    DifficultyUp,
+    LevelStarted,
+    Died,
+    Victory
+}
+
+// Where a run ended up. Replays compare these to check they played out the same way.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RunResult {
+    pub ticks: u64,
+    pub score: u32,
+    pub levels_completed: u32,
+    pub level_index: usize,
+    pub difficulty: u32,
+    pub player_x: f64,
+    pub player_y: f64
+}
+
+// Loads or generates the map for a campaign level.
+// Fails if the level's treasure goal asks for more treasure than the map has.
+pub fn load_map(level: &Level) -> error::Result<Map> {
+    let map: Map = match (&level.map, &level.generate) {
+        (&Some(ref map), _) => Map::load(map)?,
+        (&None, &Some(ref settings)) => generator::generate(settings)?,
+        (&None, &None) => return Err(Error::Config("Campaign level has no map".to_string()))
+    };
+
+    if let Some(goal) = level.treasure_goal {
+        if goal > map.count_treasure() {
+            return Err(Error::Config(format!("{} has a treasure goal of {}, but only {} treasure", level.describe(), goal, map.count_treasure())));
+        }
+    }
+
+    Ok(map)
+}
+
+// Loads every level of the campaign, reporting every one that fails together
+pub fn check_campaign(campaign: &Campaign) -> error::Result<()> {
+    let errors: Vec<Error> = campaign.levels.iter()
+        .filter_map(|level| load_map(level).err())
+        .flat_map(Error::flatten)
+        .collect();
+
+    Error::batch(errors)
+}
+
+// Everything about a run of the campaign that changes as it's played
+pub struct Simulation {
+    pub campaign: Campaign,
+    pub map: Map,
+
+    pub player_x: f64,
+    pub player_y: f64,
+    pub player_rotation: f64,
+    // Where the player was before the last tick, for interpolated rendering
+    pub previous_player_x: f64,
+    pub previous_player_y: f64,
+    pub previous_player_rotation: f64,
+    pub score: u32,
+
+    pub level_index: usize,
+    pub level_treasure_goal: u32,
+    pub level_treasure_collected: u32,
+    pub levels_completed: u32,
+    pub is_exit_open: bool,
+    // The exit opens at the spawn, so it only leads anywhere once the player has stepped off it
+    pub has_left_spawn: bool,
+    pub ticks: u64,
+    // Seconds spent playing since the run was started
+    pub play_time: f64,
+    // Seconds of play until the next pathfind and difficulty increase
+    pub pathfind_timer: f64,
+    pub difficulty_timer: f64,
+
+    pub difficulty: u32
+}
+
+impl Simulation {
+    // Nothing is loaded until the run is started
+    pub fn new(campaign: Campaign) -> Simulation {
+        Simulation {
+            campaign: campaign,
+            map: Map::new(0, 0, Vec::new(), Vec::new()),
+            player_x: 0.0,
+            player_y: 0.0,
+            player_rotation: 0.0,
+            previous_player_x: 0.0,
+            previous_player_y: 0.0,
+            previous_player_rotation: 0.0,
+            score: 0,
+            level_index: 0,
+            level_treasure_goal: 0,
+            level_treasure_collected: 0,
+            levels_completed: 0,
+            is_exit_open: false,
+            has_left_spawn: false,
+            ticks: 0,
+            play_time: 0.0,
+            pathfind_timer: 0.0,
+            difficulty_timer: 0.0,
+            difficulty: 0
+        }
+    }
+
+    // Starts the campaign over from the first level, with a fresh score
+    pub fn start(&mut self) -> error::Result<()> {
+        self.score = 0;
+        self.levels_completed = 0;
+        self.ticks = 0;
+        self.play_time = 0.0;
+
+        self.start_level(0)
+    }
+
+    // Swaps in the given campaign level and puts the player at its spawn.
+    // Score and stats carry over, everything about the level itself starts over.
+    pub fn start_level(&mut self, index: usize) -> error::Result<()> {
+        let level: Level = self.campaign.levels[index].clone();
+        let map: Map = load_map(&level)?;
+        let spawn: Spawn = map.spawn;
+
+        self.level_treasure_goal = level.treasure_goal.unwrap_or(map.count_treasure());
+        self.level_treasure_collected = 0;
+        self.is_exit_open = false;
+        self.has_left_spawn = false;
+        self.difficulty = level.difficulty.start;
+        self.pathfind_timer = 0.0;
+        self.difficulty_timer = 0.0;
+
+        self.map = map;
+        self.level_index = index;
+
+        self.player_x = spawn.x as f64 + 0.5;
+        self.player_y = spawn.y as f64 + 0.5;
+        self.player_rotation = spawn.facing.to_rotation();
+        self.previous_player_x = self.player_x;
+        self.previous_player_y = self.player_y;
+        self.previous_player_rotation = self.player_rotation;
+
+        println!("Entering {}", self.map.info.name);
+
+        // Levels without any treasure to collect start out with the exit open,
+        // though it only leads on once the player has stepped off the spawn
+        self.check_exit();
+
+        Ok(())
+    }
+
+    // Loads the current level's map again, after it's been edited.
+    // The player stays where they are if that's still open floor on the new map, and goes back to the spawn otherwise.
+    // Score and timers carry on, but the entities and the level's treasure start over from the new map.
+    pub fn reload_map(&mut self) -> error::Result<()> {
+        let level: Level = self.campaign.levels[self.level_index].clone();
+        let map: Map = load_map(&level)?;
+
+        let tile_x: i32 = self.player_x.floor() as i32;
+        let tile_y: i32 = self.player_y.floor() as i32;
+        if !map.is_inside(tile_x, tile_y) || map.is_solid(tile_x, tile_y) {
+            let spawn: Spawn = map.spawn;
+            self.player_x = spawn.x as f64 + 0.5;
+            self.player_y = spawn.y as f64 + 0.5;
+            self.player_rotation = spawn.facing.to_rotation();
+            println!("The player is no longer on open floor, moving them back to the spawn");
+        }
+
+        self.previous_player_x = self.player_x;
+        self.previous_player_y = self.player_y;
+        self.previous_player_rotation = self.player_rotation;
+
+        self.level_treasure_goal = level.treasure_goal.unwrap_or(map.count_treasure());
+        self.level_treasure_collected = 0;
+        self.is_exit_open = false;
+        self.map = map;
+        self.has_left_spawn = !self.is_on_spawn();
+
+        println!("Reloaded {}", self.map.info.name);
+
+        self.check_exit();
+
+        Ok(())
+    }
+
+    // Opens the exit (at the spawn) once enough treasure has been collected
+    fn check_exit(&mut self) {
+        if self.is_exit_open || (self.level_treasure_collected < self.level_treasure_goal) {
+            return;
+        }
+
+        let spawn: Spawn = self.map.spawn;
+        let texture_id: u32 = self.map.texture_id(TEXTURE_EXIT);
+        self.map.add_ent(spawn.x as f64, spawn.y as f64, texture_id, Archetype::Exit, false);
+        self.is_exit_open = true;
+
+        println!("The exit has opened!");
+    }
+
+    pub fn is_on_spawn(&self) -> bool {
+        let spawn: Spawn = self.map.spawn;
+        (self.player_x.floor() as i32 == spawn.x as i32) && (self.player_y.floor() as i32 == spawn.y as i32)
+    }
+
+    pub fn result(&self) -> RunResult {
+        RunResult {
+            ticks: self.ticks,
+            score: self.score,
+            levels_completed: self.levels_completed,
+            level_index: self.level_index,
+            difficulty: self.difficulty,
+            player_x: self.player_x,
+            player_y: self.player_y
+        }
+    }
+
+    // Moves everything along by one tick of play.
+    // Only fails if the next level can't be loaded.
+    pub fn tick(&mut self, input: &TickInput) -> error::Result<Vec<SimEvent>> {
+        let mut events: Vec<SimEvent> = Vec::new();
+
+        let delta_time: f64 = TICK_SECONDS;
+        self.ticks += 1;
+        self.play_time += delta_time;
+        self.pathfind_timer += delta_time;
+        self.difficulty_timer += delta_time;
+
+        self.previous_player_x = self.player_x;
+        self.previous_player_y = self.player_y;
+        self.previous_player_rotation = self.player_rotation;
+
+        let move_speed: f64 = 2.0 + (self.score as f64 * 0.15);
+
+        self.player_rotation = wrap_angle(self.player_rotation + input.turn);
+
+        // Calculate velocity based on input
+        let velocity_x: f64 = ((self.player_rotation.cos() * input.forward) + (f64::cos(self.player_rotation + (::std::f64::consts::PI / 2.0)) * input.strafe)) * move_speed;
+        let velocity_y: f64 = ((self.player_rotation.sin() * input.forward) + (f64::sin(self.player_rotation + (::std::f64::consts::PI / 2.0)) * input.strafe)) * move_speed;
+
+        // Apply velocity
+        if (velocity_x != 0.0) || (velocity_y != 0.0) {
+            let new_position_x = self.player_x + (velocity_x * delta_time);
+            let new_position_y = self.player_y + (velocity_y * delta_time);
+
+            if !self.map.is_solid(new_position_x.trunc() as i32, self.player_y.trunc() as i32) {
+                self.player_x = new_position_x;
+            }
+
+            if !self.map.is_solid(self.player_x.trunc() as i32, new_position_y.trunc() as i32) {
+                self.player_y = new_position_y;
+            }
+        }
+
+        if !self.has_left_spawn && !self.is_on_spawn() {
+            self.has_left_spawn = true;
+        }
+
+        if self.pathfind_timer >= PATHFIND_INTERVAL {
+            self.pathfind_timer -= PATHFIND_INTERVAL;
+            let goal = Position::new(self.player_x as i32, self.player_y as i32);
+            self.map.pathfind(goal);
+        }
+
+        let difficulty_curve = self.campaign.levels[self.level_index].difficulty;
+        let wait: f64 = difficulty_curve.interval_at(self.difficulty);
+        if self.difficulty_timer >= wait {
+            self.difficulty_timer -= wait;
+
+            // Increase difficulty
+            self.difficulty += 1;
+            events.push(SimEvent::DifficultyUp);
+        }
+
+        let ent_speed: f64 = difficulty_curve.entity_speed(self.difficulty);
+        self.map.entity_movement(ent_speed, delta_time);
+
+        if let Some(ent) = self.map.get_overlap_ent(self.player_x, self.player_y) {
+            if self.map.is_treasure(ent) {
+                self.map.delete_ent(ent);
+                self.score += 1;
+                self.level_treasure_collected += 1;
+                events.push(SimEvent::TreasureCollected);
+                self.check_exit();
+            }
+            else if self.map.is_exit(ent) && self.has_left_spawn {
+                self.levels_completed += 1;
+
+                if self.level_index + 1 < self.campaign.levels.len() {
+                    let next_level: usize = self.level_index + 1;
+                    self.start_level(next_level)?;
+                    events.push(SimEvent::LevelStarted);
+                }
+                else {
+                    events.push(SimEvent::Victory);
+                }
+            }
+            else if self.map.is_deadly(ent) {
+                events.push(SimEvent::Died);
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+// Plays a run without a window, until the inputs run out, the run ends, or max_ticks have passed
+pub fn run_headless<I>(campaign: Campaign, inputs: I, max_ticks: u64) -> error::Result<RunResult> where I: Iterator<Item = TickInput> {
+    let mut simulation: Simulation = Simulation::new(campaign);
+    simulation.start()?;
+
+    for input in inputs.take(max_ticks as usize) {
+        let events: Vec<SimEvent> = simulation.tick(&input)?;
+        if events.contains(&SimEvent::Died) || events.contains(&SimEvent::Victory) {
+            break;
+        }
+    }
+
+    Ok(simulation.result())
+}
+
+#[cfg(test)]
+mod tests {
+    use campaign::Campaign;
+    use generator::GeneratorSettings;
+    use super::{Simulation, SimEvent, TickInput, check_campaign};
+
+    fn campaign(treasure_goal: Option<u32>) -> Campaign {
+        let mut campaign: Campaign = Campaign::generated(GeneratorSettings {
+            seed: 7,
+            treasure_count: 5,
+            monster_count: 0,
+            ..GeneratorSettings::default()
+        });
+        campaign.levels[0].treasure_goal = treasure_goal;
+        campaign
+    }
+
+    #[test]
+    fn exit_at_the_spawn_waits_for_the_player_to_step_off() {
+        let mut simulation: Simulation = Simulation::new(campaign(Some(0)));
+        simulation.start().unwrap();
+        assert!(simulation.is_exit_open);
+
+        for _ in 0..120 {
+            assert!(simulation.tick(&TickInput::default()).unwrap().is_empty());
+        }
+
+        let (spawn_x, spawn_y) = (simulation.player_x, simulation.player_y);
+        let (open_x, open_y) = [(1.0, 0.0), (-1.0, 0.0), (0.0, 1.0), (0.0, -1.0)].iter()
+            .map(|&(x, y)| (spawn_x + x, spawn_y + y))
+            .find(|&(x, y)| !simulation.map.is_solid(x as i32, y as i32))
+            .unwrap();
+
+        simulation.player_x = open_x;
+        simulation.player_y = open_y;
+        simulation.tick(&TickInput::default()).unwrap();
+
+        simulation.player_x = spawn_x;
+        simulation.player_y = spawn_y;
+        assert_eq!(simulation.tick(&TickInput::default()).unwrap(), vec![SimEvent::Victory]);
+    }
+
+    #[test]
+    fn rejects_treasure_goals_over_the_treasure_on_the_map() {
+        assert!(check_campaign(&campaign(Some(5))).is_ok());
+
+        let message: String = check_campaign(&campaign(Some(6))).unwrap_err().to_string();
+        assert!(message.contains("treasure goal of 6"));
+    }
+}
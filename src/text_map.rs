@@ -0,0 +1,149 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use map::{Map, MapInfo, Spawn, Facing};
+use legend::Legend;
+use error;
+use error::Error;
+
+// Text maps are a plain grid of characters, which is much easier to diff and merge than an image.
+// A header of "key = value" lines comes first, then a "---" line, then one row of the grid per line:
+//
+//     name = Level 1
+//     music = theme
+//     spawn = 1 1 east
+//     ---
+//     #####
+//     #.T.#
+//     #####
+//
+// Each character is looked up by symbol in the legend next to the map (the same legend image maps use),
+// and "." is empty floor. The spawn can either be in the header or marked on the grid with a spawn symbol.
+
+pub const EMPTY_SYMBOL: char = '.';
+pub const HEADER_END: &'static str = "---";
+
+fn invalid_data(file_path: &str, message: String) -> Error {
+    Error::InvalidMap(format!("Invalid text map {}: {}", file_path, message))
+}
+
+// Parses "<x> <y> <facing>"
+fn parse_spawn(value: &str) -> Option<Spawn> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    let x: u32 = parts[0].parse().ok()?;
+    let y: u32 = parts[1].parse().ok()?;
+    let facing: Facing = Facing::from_name(parts[2])?;
+
+    Some(Spawn::new(x, y, facing))
+}
+
+impl Map {
+    // Loads a text map, along with the legend next to it that says what each character means
+    pub fn load_text(file_path: &str) -> error::Result<Map> {
+        let mut contents: String = String::new();
+        File::open(file_path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| Error::missing_asset(file_path, e))?;
+
+        let legend: Legend = Legend::load(&Legend::path_for_map(Path::new(file_path)))?;
+        let mut info: MapInfo = legend.map.clone();
+
+        let mut lines = contents.lines();
+
+        // Header
+        loop {
+            let line: &str = match lines.next() {
+                Some(line) => line.trim(),
+                None => return Err(invalid_data(file_path, format!("missing '{}' after the header", HEADER_END)))
+            };
+
+            if line == HEADER_END {
+                break;
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key: &str = parts.next().unwrap_or("").trim();
+            let value: &str = match parts.next() {
+                Some(value) => value.trim(),
+                None => return Err(invalid_data(file_path, format!("expected 'key = value' but found '{}'", line)))
+            };
+
+            match key {
+                "name" => info.name = value.to_string(),
+                "music" => info.music = Some(value.to_string()),
+                "spawn" => info.spawn = Some(parse_spawn(value).ok_or_else(|| {
+                    invalid_data(file_path, format!("expected 'spawn = <x> <y> <north|east|south|west>' but found '{}'", line))
+                })?),
+                _ => return Err(invalid_data(file_path, format!("unknown header key '{}'", key)))
+            }
+        }
+
+        // Grid
+        let rows: Vec<Vec<char>> = lines
+            .map(|line| line.trim_end().chars().collect::<Vec<char>>())
+            .filter(|row| !row.is_empty())
+            .collect();
+
+        let height: u32 = rows.len() as u32;
+        let width: u32 = rows.first().map_or(0, |row| row.len() as u32);
+
+        if let Some(y) = rows.iter().position(|row| row.len() as u32 != width) {
+            return Err(invalid_data(file_path, format!("row {} is {} tiles wide, but the map is {} tiles wide", y, rows[y].len(), width)));
+        }
+
+        Map::build(width, height, legend, info, |legend, x, y| {
+            let symbol: char = rows[y as usize][x as usize];
+            if symbol == EMPTY_SYMBOL {
+                return Ok(None);
+            }
+
+            match legend.find_symbol(symbol) {
+                Some(entry) => Ok(Some(entry)),
+                None => Err(invalid_data(file_path, format!("'{}' at {}, {} isn't in the legend", symbol, x, y)))
+            }
+        })
+    }
+
+    // Saves the map as a text map, along with its legend
+    pub fn save_text(&self, file_path: &str) -> error::Result<()> {
+        let mut contents: String = String::new();
+
+        contents.push_str(&format!("name = {}\n", self.info.name));
+        if let Some(ref music) = self.info.music {
+            contents.push_str(&format!("music = {}\n", music));
+        }
+        if let Some(ref spawn) = self.info.spawn {
+            contents.push_str(&format!("spawn = {} {} {}\n", spawn.x, spawn.y, spawn.facing.name()));
+        }
+        contents.push_str(HEADER_END);
+        contents.push('\n');
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let symbol: char = match self.get_legend_entry(x, y)? {
+                    Some(entry) => entry.symbol(),
+                    None => EMPTY_SYMBOL
+                };
+
+                contents.push(symbol);
+            }
+
+            contents.push('\n');
+        }
+
+        File::create(file_path)?.write_all(contents.as_bytes())?;
+
+        let mut legend: Legend = self.legend.clone();
+        legend.map = self.info.clone();
+        legend.save(&Legend::path_for_map(Path::new(file_path)))?;
+        Ok(())
+    }
+}
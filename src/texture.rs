@@ -0,0 +1,159 @@
+use std::io;
+use std::path::Path;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::surface::Surface;
+use sdl2::image::{LoadSurface, SaveSurface};
+use error;
+use error::Error;
+
+// Textures are read from and saved to surfaces as RGBA32, which is r, g, b, a bytes in memory on every platform.
+// SDL defines it as whichever packed format has that byte order on the target, and this version of sdl2 doesn't name it.
+#[cfg(target_endian = "little")]
+const RGBA32: PixelFormatEnum = PixelFormatEnum::ABGR8888;
+#[cfg(target_endian = "big")]
+const RGBA32: PixelFormatEnum = PixelFormatEnum::RGBA8888;
+
+const PIXEL_FORMAT: PixelFormatEnum = RGBA32;
+
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>
+}
+
+impl Texture {
+    pub fn new(width: u32, height: u32, pixels: Vec<Color>) -> Texture {
+        Texture {
+            width: width,
+            height: height,
+            pixels: pixels
+        }
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        self.pixels[((y * self.width) + x) as usize]
+    }
+
+    // Loads an image in any format SDL_image can read. 24-bit, palettized and color keyed images
+    // are converted to RGBA first, so every texture ends up with the same colors the image shows.
+    pub fn load(file_path: &str) -> error::Result<Texture> {
+        let sdl_surface: Surface = Surface::from_file(Path::new(file_path))
+            .map_err(|e| Error::missing_asset(file_path, e))?;
+
+        let unsupported = || Error::UnsupportedPixelFormat {
+            path: file_path.to_string(),
+            format: sdl_surface.pixel_format_enum()
+        };
+
+        // SDL only converts to a format it has a PixelFormat for, which comes from a surface
+        let rgba_surface: Surface = Surface::new(1, 1, PIXEL_FORMAT)
+            .and_then(|target| sdl_surface.convert(&target.pixel_format()))
+            .map_err(|_| unsupported())?;
+
+        let width: u32 = rgba_surface.width();
+        let height: u32 = rgba_surface.height();
+        let pitch: usize = rgba_surface.pitch() as usize;
+
+        let mut pixels: Vec<Color> = Vec::with_capacity((width * height) as usize);
+
+        // Read the pixels row by row. Rows can be padded past width * 4 bytes, so each starts at a multiple of the pitch.
+        rgba_surface.with_lock(|surface_buffer: &[u8]| {
+            for y in 0..height as usize {
+                let row: &[u8] = &surface_buffer[(y * pitch)..((y * pitch) + (width as usize * 4))];
+
+                for pixel in row.chunks(4) {
+                    pixels.push(Color {
+                        r: pixel[0],
+                        g: pixel[1],
+                        b: pixel[2],
+                        a: pixel[3]
+                    });
+                }
+            }
+        });
+
+        Ok(Texture::new(width, height, pixels))
+    }
+
+    // Saves the texture as a PNG image
+    pub fn save(&self, file_path: &str) -> std::io::Result<()> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(self.pixels.len() * 4);
+        for color in self.pixels.iter() {
+            bytes.push(color.r);
+            bytes.push(color.g);
+            bytes.push(color.b);
+            bytes.push(color.a);
+        }
+
+        let sdl_surface: Surface = Surface::from_data(&mut bytes, self.width, self.height, self.width * 4, PIXEL_FORMAT)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        sdl_surface.save(Path::new(file_path))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Failed to save texture {}: {}", file_path, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::Color;
+    use super::Texture;
+
+    // Every fixture is 3x2 pixels of the same colors, stored a different way
+    const FIXTURES_DIR: &'static str = "tests/fixtures/textures";
+
+    const COLORS: [[(u8, u8, u8); 3]; 2] = [
+        [(255, 0, 0), (0, 255, 0), (0, 0, 255)],
+        [(255, 255, 255), (0, 0, 0), (12, 34, 56)]
+    ];
+
+    fn load(name: &str) -> Texture {
+        Texture::load(&format!("{}/{}", FIXTURES_DIR, name)).unwrap()
+    }
+
+    // Checks every pixel has the fixture's color, with the given alpha
+    fn check_colors(texture: &Texture, alpha: [[u8; 3]; 2]) {
+        assert_eq!((texture.width, texture.height), (3, 2));
+
+        for y in 0..2 {
+            for x in 0..3 {
+                let (r, g, b) = COLORS[y][x];
+                assert_eq!(texture.get_pixel(x as u32, y as u32), Color::RGBA(r, g, b, alpha[y][x]), "pixel {}, {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn loads_24_bit_images() {
+        // Each row is 9 bytes, which SDL pads out to 12
+        check_colors(&load("rgb24.png"), [[255; 3]; 2]);
+    }
+
+    #[test]
+    fn loads_palettized_images() {
+        check_colors(&load("palettized.png"), [[255; 3]; 2]);
+    }
+
+    #[test]
+    fn loads_bgra_images() {
+        check_colors(&load("bgra.bmp"), [[255, 255, 255], [255, 255, 128]]);
+    }
+
+    #[test]
+    fn color_key_becomes_transparent() {
+        // Magenta is keyed out in place of the black pixel
+        let texture: Texture = load("color_keyed.png");
+
+        for y in 0..2 {
+            for x in 0..3 {
+                let color: Color = texture.get_pixel(x, y);
+                if (x, y) == (1, 1) {
+                    assert_eq!(color.a, 0);
+                }
+                else {
+                    let (r, g, b) = COLORS[y as usize][x as usize];
+                    assert_eq!(color, Color::RGBA(r, g, b, 255), "pixel {}, {}", x, y);
+                }
+            }
+        }
+    }
+}
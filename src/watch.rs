@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::SystemTime;
+
+// Notices when files change, by checking their modification times whenever it's asked.
+// Polling a few dozen files is cheap, and works the same everywhere.
+pub struct FileWatcher {
+    // None for files that don't exist (yet)
+    modified: HashMap<String, Option<SystemTime>>
+}
+
+fn modified_time(file_path: &str) -> Option<SystemTime> {
+    fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl Default for FileWatcher {
+    fn default() -> FileWatcher {
+        FileWatcher::new()
+    }
+}
+
+impl FileWatcher {
+    pub fn new() -> FileWatcher {
+        FileWatcher {
+            modified: HashMap::new()
+        }
+    }
+
+    // Stops watching every file
+    pub fn clear(&mut self) {
+        self.modified.clear();
+    }
+
+    // Starts watching a file, as it is now
+    pub fn watch(&mut self, file_path: &str) {
+        self.modified.insert(file_path.to_string(), modified_time(file_path));
+    }
+
+    // The watched files that have changed since they were last checked
+    pub fn changed(&mut self) -> Vec<String> {
+        let mut changed: Vec<String> = Vec::new();
+
+        for (file_path, modified) in self.modified.iter_mut() {
+            let current: Option<SystemTime> = modified_time(file_path);
+            if current != *modified {
+                *modified = current;
+                changed.push(file_path.clone());
+            }
+        }
+
+        changed.sort();
+        changed
+    }
+}